@@ -0,0 +1,868 @@
+use anyhow::Context;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use http_recorder::{
+    archive::{
+        writer::{Rotation, WriteOptions, MAX_PACK},
+        Codec, Compression, RecordingReader,
+    },
+    content::Content,
+    header::Headers,
+    request,
+    snippet::Language,
+    websocket, Entry,
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+mod replay;
+
+#[derive(Parser)]
+#[command(name = "http-recorder", version, about = "Inspect http recordings")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List entries of a recording
+    Ls { recording: PathBuf },
+    /// Show headers, cookies and body previews of an entry
+    Show {
+        recording: PathBuf,
+        index: u32,
+        /// Maximum number of body bytes to preview
+        #[arg(long, default_value_t = 1024)]
+        preview: usize,
+    },
+    /// Write a request or response body to stdout
+    Cat(CatArgs),
+    /// Export a recording as HAR 1.2
+    ExportHar {
+        recording: PathBuf,
+        /// Output file, stdout if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Export a recording as a mitmproxy flow dump
+    ExportMitmproxy {
+        recording: PathBuf,
+        /// Output file, stdout if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Export a recording as WARC 1.1 request and response records
+    ExportWarc {
+        recording: PathBuf,
+        /// Output file, stdout if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Compress every record as its own gzip member, as in .warc.gz files
+        #[arg(long)]
+        gzip: bool,
+    },
+    /// Convert a HAR file into a recording
+    ImportHar {
+        har: PathBuf,
+        /// Directory of the new recording
+        dest: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Convert mitmproxy flow dumps into recordings
+    ImportMitmproxy(ImportMitmproxyArgs),
+    /// Convert the request and response records of a WARC file, plain or
+    /// gzip compressed, into a recording
+    ImportWarc {
+        warc: PathBuf,
+        /// Directory of the new recording
+        dest: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Rebuild recordings from spill directories left by a crashed recorder
+    Recover(RecoverArgs),
+    /// Copy a recording with secrets masked
+    Redact(RedactArgs),
+    /// Print a command line or code that sends the request of an entry again
+    Snippet {
+        recording: PathBuf,
+        index: u32,
+        /// curl, httpie or reqwest
+        #[arg(short, long, default_value_t = Language::Curl)]
+        language: Language,
+    },
+    /// Count the TLS versions and cipher suites negotiated with each server
+    Tls { recording: PathBuf },
+    /// Serve recorded responses over http
+    Replay {
+        recording: PathBuf,
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        listen: String,
+        /// Which parts of a request have to equal the recorded one
+        #[arg(long, value_enum, default_value_t = replay::Strictness::Query)]
+        strictness: replay::Strictness,
+        /// Match requests regardless of their host. Without it the host is
+        /// compared for requests naming another host than the replay server,
+        /// e.g. sent through it as a proxy; requests to localhost or the
+        /// listen address match recorded ones of any host
+        #[arg(long)]
+        ignore_host: bool,
+        /// Status code sent for requests without a recorded response
+        #[arg(long, default_value_t = 404)]
+        unmatched_status: u16,
+    },
+}
+
+#[derive(Args)]
+struct RedactArgs {
+    recording: PathBuf,
+    /// Directory of the redacted copy
+    dest: PathBuf,
+    /// Header whose values are masked, may be repeated
+    #[arg(long = "header")]
+    headers: Vec<String>,
+    /// Cookie whose values are masked, may be repeated
+    #[arg(long = "cookie")]
+    cookies: Vec<String>,
+    /// Query or form parameter whose values are masked, may be repeated
+    #[arg(long = "param")]
+    params: Vec<String>,
+    /// Dotted path of a JSON field to mask, `*` matches any key or index
+    #[arg(long = "json-path")]
+    json_paths: Vec<String>,
+    #[command(flatten)]
+    write: WriteArgs,
+}
+
+#[derive(Args)]
+struct RecoverArgs {
+    /// Directory the recovered recordings are written to, one per spill directory
+    dest: PathBuf,
+    /// Spill directories to recover, all leftover ones in the temp dir if omitted
+    spill_dirs: Vec<PathBuf>,
+    /// Where to look for leftover spill directories
+    #[arg(long, conflicts_with = "spill_dirs")]
+    tmp_dir: Option<PathBuf>,
+    /// Remove spill directories that were recovered without skipping any file
+    #[arg(long)]
+    remove: bool,
+    #[command(flatten)]
+    write: WriteArgs,
+}
+
+#[derive(Args)]
+struct ImportMitmproxyArgs {
+    /// Directory the recordings are written to, one per dump named after its file
+    dest: PathBuf,
+    /// Flow dumps written by `mitmdump -w`
+    #[arg(required = true)]
+    dumps: Vec<PathBuf>,
+    #[command(flatten)]
+    write: WriteArgs,
+}
+
+#[derive(Args)]
+struct WriteArgs {
+    /// Store identical bodies only once
+    #[arg(long)]
+    dedup: bool,
+    /// Compression of packs and blobs: xz, zstd or none
+    #[arg(long, default_value_t = Codec::Xz)]
+    codec: Codec,
+    /// Compression level, the default of the codec if omitted
+    #[arg(long)]
+    level: Option<i32>,
+    /// Body bytes after which a pack is closed, 0 for no limit
+    #[arg(long, default_value_t = MAX_PACK)]
+    pack_size: u64,
+    /// Number of entries after which a pack is closed
+    #[arg(long)]
+    pack_entries: Option<u32>,
+    /// Seconds of entry start times covered by a pack, e.g. 3600 for hourly packs
+    #[arg(long)]
+    pack_interval: Option<u64>,
+}
+impl WriteArgs {
+    fn options(&self) -> anyhow::Result<WriteOptions> {
+        Ok(WriteOptions {
+            dedup: self.dedup,
+            compression: Compression::new(self.codec, self.level)?,
+            rotation: Rotation {
+                max_size: Some(self.pack_size).filter(|s| *s != 0),
+                max_entries: self.pack_entries,
+                interval: self.pack_interval.map(Duration::from_secs),
+            },
+        })
+    }
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("body").required(true).args(["request_body", "response_body"])))]
+struct CatArgs {
+    recording: PathBuf,
+    index: u32,
+    #[arg(long)]
+    request_body: bool,
+    #[arg(long)]
+    response_body: bool,
+    /// Field of a multipart request body
+    #[arg(long, requires = "request_body")]
+    field: Option<usize>,
+}
+
+fn duration_ms(entry: &Entry) -> i64 {
+    (entry.timings.finish_time - entry.timings.start_time).num_milliseconds()
+}
+
+fn ls_row<W: Write>(out: &mut W, entry: &Entry) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>8} {:<7} {:>6} {:<32} {:>10} {:>6}ms  {}",
+        entry.index,
+        entry.request.method.as_str(),
+        entry
+            .response
+            .as_ref()
+            .map_or(String::from("ERR"), |r| r.status_code.0.to_string()),
+        entry
+            .request
+            .url
+            .host
+            .as_ref()
+            .map_or(String::from("-"), |h| h.to_string()),
+        entry
+            .response
+            .as_ref()
+            .and_then(|r| r.content.as_ref())
+            .map_or(0, |c| c.size),
+        duration_ms(entry),
+        entry.request.url.path,
+    )
+}
+
+fn ls(recording: PathBuf) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(&recording).context("failed to open recording")?;
+    let mut out = io::stdout().lock();
+    writeln!(
+        out,
+        "{:>8} {:<7} {:>6} {:<32} {:>10} {:>8}  PATH",
+        "INDEX", "METHOD", "STATUS", "HOST", "SIZE", "TIME"
+    )?;
+    for entry in reader.entries() {
+        let entry = entry.context("failed to read entry")?;
+        ls_row(&mut out, &entry)?;
+    }
+    if let Some(journal) = reader.journal().context("failed to read journal")? {
+        eprintln!(
+            "pack {} was not finished, listing the {} entries its journal marks complete",
+            journal.pack,
+            journal.records.len()
+        );
+        for entry in reader
+            .journaled_entries(&journal)
+            .context("failed to open unfinished pack")?
+        {
+            let entry = entry.context("failed to read entry")?;
+            ls_row(&mut out, &entry)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_headers<W: Write>(out: &mut W, headers: &Headers) -> io::Result<()> {
+    for h in headers.0.iter() {
+        writeln!(out, "  {}: {}", h.name.as_str(), h.value.to_string_lossy())?;
+    }
+    Ok(())
+}
+fn print_content<W: Write>(out: &mut W, content: &Content, preview: usize) -> io::Result<()> {
+    writeln!(out, "  {} ({} bytes)", content.content_type, content.size)?;
+    let data = match &content.data {
+        Some(d) => d.as_ref(),
+        None => return Ok(()),
+    };
+    let shown = &data[..data.len().min(preview)];
+    let text = match std::str::from_utf8(shown) {
+        Ok(s) => s,
+        // preview cut inside a multi-byte character
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&shown[..e.valid_up_to()]).unwrap()
+        }
+        Err(_) => return writeln!(out, "  <binary data>"),
+    };
+    for line in text.lines() {
+        writeln!(out, "  | {}", line)?;
+    }
+    if shown.len() < data.len() {
+        writeln!(out, "  ... {} more bytes", data.len() - shown.len())?;
+    }
+    Ok(())
+}
+
+fn print_tls<W: Write>(out: &mut W, side: &str, tls: &http_recorder::tls::Tls) -> io::Result<()> {
+    let unknown = |v: &Option<String>| v.clone().unwrap_or_else(|| String::from("-"));
+    writeln!(
+        out,
+        "{} tls {}, cipher {}, alpn {}, sni {}",
+        side,
+        unknown(&tls.version),
+        unknown(&tls.cipher),
+        unknown(&tls.alpn),
+        unknown(&tls.sni)
+    )?;
+    for c in tls.certificates.iter() {
+        writeln!(
+            out,
+            "  {} issued by {}, valid {} to {}",
+            c.common_name().unwrap_or("-"),
+            c.issuer
+                .iter()
+                .find(|(k, _)| k == "CN")
+                .map_or("-", |(_, v)| v.as_str()),
+            c.not_before,
+            c.not_after
+        )?;
+        if !c.alt_names.is_empty() {
+            writeln!(out, "    alt names: {}", c.alt_names.join(", "))?;
+        }
+        writeln!(out, "    sha256 {}", c.fingerprint)?;
+    }
+    Ok(())
+}
+
+fn print_websocket<W: Write>(
+    out: &mut W,
+    ws: &websocket::WebSocket,
+    preview: usize,
+) -> io::Result<()> {
+    writeln!(out, "\nWebSocket messages:")?;
+    if let Some(s) = &ws.segment {
+        writeln!(
+            out,
+            "  segment: message {} of the connection of #{}{}",
+            s.offset,
+            s.first,
+            if s.last { ", last" } else { "" }
+        )?;
+    }
+    for m in ws.messages.iter() {
+        let arrow = match m.direction {
+            websocket::Direction::ClientToServer => '>',
+            websocket::Direction::ServerToClient => '<',
+        };
+        match m.text() {
+            Some(t) => {
+                let end = (0..=t.len().min(preview))
+                    .rev()
+                    .find(|i| t.is_char_boundary(*i))
+                    .unwrap_or(0);
+                write!(out, "  {} {} {}", arrow, m.time, &t[..end])?;
+                if end < t.len() {
+                    write!(out, " ... {} more bytes", t.len() - end)?;
+                }
+                writeln!(out)?;
+            }
+            None => writeln!(
+                out,
+                "  {} {} <{:?}, {} bytes>",
+                arrow,
+                m.time,
+                m.opcode,
+                m.data.len()
+            )?,
+        }
+    }
+    match ws.close_code {
+        Some(code) => writeln!(
+            out,
+            "closed by {} with {}{}",
+            match ws.closed_by_client {
+                Some(true) => "client",
+                Some(false) => "server",
+                None => "unknown",
+            },
+            code,
+            ws.close_reason
+                .as_deref()
+                .filter(|r| !r.is_empty())
+                .map_or(String::new(), |r| format!(": {}", r))
+        ),
+        None => writeln!(out, "closed without close frame"),
+    }
+}
+
+fn show(recording: PathBuf, index: u32, preview: usize) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(&recording).context("failed to open recording")?;
+    let entry = reader.get(index).context("failed to read entry")?;
+    let mut out = io::stdout().lock();
+    let request = &entry.request;
+    writeln!(
+        out,
+        "#{} {} {} {}",
+        entry.index,
+        request.method.as_str(),
+        request.url.url,
+        request.http_version.as_str()
+    )?;
+    writeln!(
+        out,
+        "started {} ({}ms)",
+        entry.timings.start_time,
+        duration_ms(&entry)
+    )?;
+    if let Some(p) = &entry.timings.phases {
+        let phases = [
+            ("dns", p.dns),
+            ("connect", p.connect),
+            ("tls", p.tls),
+            ("send", p.send),
+            ("wait", p.wait),
+            ("receive", p.receive),
+        ]
+        .into_iter()
+        .filter_map(|(name, t)| t.map(|t| format!("{} {:.1}ms", name, t)))
+        .collect::<Vec<_>>();
+        if !phases.is_empty() {
+            writeln!(out, "phases: {}", phases.join(", "))?;
+        }
+    }
+    write!(out, "client {}", entry.client_addr)?;
+    match entry.server_addr {
+        Some(a) => writeln!(out, ", server {}", a)?,
+        None => writeln!(out)?,
+    }
+    if let Some(t) = &entry.client_tls {
+        print_tls(&mut out, "client", t)?;
+    }
+    if let Some(t) = &entry.server_tls {
+        print_tls(&mut out, "server", t)?;
+    }
+
+    writeln!(out, "\nRequest headers:")?;
+    print_headers(&mut out, &request.headers)?;
+    if !request.cookies.0.is_empty() {
+        writeln!(out, "Request cookies:")?;
+        for c in request.cookies.0.iter() {
+            writeln!(out, "  {}={}", c.name, c.value)?;
+        }
+    }
+    match &request.body {
+        Some(request::Body::Content(c)) => {
+            writeln!(out, "Request body:")?;
+            print_content(&mut out, c, preview)?;
+        }
+        Some(request::Body::UrlEncodedForm(f)) => {
+            writeln!(out, "Request form:")?;
+            for e in f.iter() {
+                writeln!(out, "  {}={}", e.name, e.value)?;
+            }
+        }
+        Some(request::Body::MultipartForm(f)) => {
+            writeln!(out, "Request multipart form:")?;
+            for (idx, e) in f.iter().enumerate() {
+                writeln!(
+                    out,
+                    "  [{}] name={} file_name={}",
+                    idx,
+                    e.name.as_deref().unwrap_or("-"),
+                    e.file_name.as_deref().unwrap_or("-")
+                )?;
+                print_content(&mut out, &e.content, preview)?;
+            }
+        }
+        None => (),
+    }
+
+    if let Some(e) = &entry.error {
+        writeln!(out, "\nError at {}: {}", e.time, e.message)?;
+    }
+    let response = match &entry.response {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+    writeln!(
+        out,
+        "\n{} {}",
+        response.http_version.as_str(),
+        response.status_code.0
+    )?;
+    writeln!(out, "Response headers:")?;
+    print_headers(&mut out, &response.headers)?;
+    if !response.cookies.0.is_empty() {
+        writeln!(out, "Response cookies:")?;
+        for c in response.cookies.0.iter() {
+            write!(out, "  {}={}", c.name, c.value)?;
+            if let Some(d) = &c.domain {
+                write!(out, "; Domain={}", d)?;
+            }
+            if let Some(p) = &c.path {
+                write!(out, "; Path={}", p)?;
+            }
+            if let Some(s) = &c.same_site {
+                write!(out, "; SameSite={:?}", s)?;
+            }
+            if c.secure == Some(true) {
+                write!(out, "; Secure")?;
+            }
+            if c.http_only == Some(true) {
+                write!(out, "; HttpOnly")?;
+            }
+            writeln!(out)?;
+        }
+    }
+    if let Some(s) = &response.segment {
+        writeln!(
+            out,
+            "Response segment: byte {} of the body of #{}{}",
+            s.offset,
+            s.first,
+            if s.last { ", last" } else { "" }
+        )?;
+    }
+    if let Some(c) = &response.content {
+        writeln!(out, "Response body:")?;
+        print_content(&mut out, c, preview)?;
+    }
+    if let Some(events) = &response.events {
+        writeln!(out, "Response events ({}):", events.len())?;
+        for e in events.iter() {
+            writeln!(
+                out,
+                "  #{} {}: {}",
+                e.id.as_deref().unwrap_or("-"),
+                e.event.as_deref().unwrap_or("message"),
+                e.data.replace('\n', "\\n")
+            )?;
+        }
+    }
+    if let Some(ws) = &entry.websocket {
+        print_websocket(&mut out, ws, preview)?;
+    }
+    Ok(())
+}
+
+fn cat(args: CatArgs) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(&args.recording).context("failed to open recording")?;
+    let entry = reader.get(args.index).context("failed to read entry")?;
+    let data: Option<Vec<u8>> = if args.response_body {
+        entry
+            .response
+            .and_then(|r| r.content)
+            .and_then(|c| c.data)
+            .map(|d| d.into_vec())
+    } else {
+        match (entry.request.body, args.field) {
+            (Some(request::Body::Content(c)), None) => c.data.map(|d| d.into_vec()),
+            (Some(request::Body::UrlEncodedForm(f)), None) => {
+                Some(request::UrlEncodedFormEntry::encode(&f).into_bytes())
+            }
+            (Some(request::Body::MultipartForm(f)), Some(idx)) => f
+                .into_iter()
+                .nth(idx)
+                .with_context(|| format!("no multipart field {}", idx))?
+                .content
+                .data
+                .map(|d| d.into_vec()),
+            (Some(request::Body::MultipartForm(_)), None) => {
+                anyhow::bail!("multipart request body, select a part with --field")
+            }
+            (Some(_), Some(_)) => anyhow::bail!("request body is not a multipart form"),
+            (None, _) => None,
+        }
+    };
+    let data = data.context("entry has no such body")?;
+    let mut out = io::stdout().lock();
+    out.write_all(&data)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn export_har(recording: PathBuf, output: Option<PathBuf>) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(&recording).context("failed to open recording")?;
+    let out: Box<dyn Write> = match output {
+        Some(p) => Box::new(fs::File::create(p).context("failed to create output file")?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = http_recorder::har::HarWriter::new(io::BufWriter::new(out))
+        .context("failed to write har")?;
+    for entry in reader.entries() {
+        writer
+            .write_entry(&entry.context("failed to read entry")?)
+            .context("failed to write har entry")?;
+    }
+    writer.finish().context("failed to finish har")?;
+    Ok(())
+}
+
+fn export_mitmproxy(recording: PathBuf, output: Option<PathBuf>) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(&recording).context("failed to open recording")?;
+    let out: Box<dyn Write> = match output {
+        Some(p) => Box::new(fs::File::create(p).context("failed to create output file")?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = http_recorder::mitmproxy::FlowWriter::new(io::BufWriter::new(out));
+    for entry in reader.entries() {
+        writer
+            .write_entry(&entry.context("failed to read entry")?)
+            .context("failed to write flow")?;
+    }
+    writer.finish().context("failed to finish flow dump")?;
+    Ok(())
+}
+
+fn export_warc(recording: PathBuf, output: Option<PathBuf>, gzip: bool) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(&recording).context("failed to open recording")?;
+    let out: Box<dyn Write> = match output {
+        Some(p) => Box::new(fs::File::create(p).context("failed to create output file")?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = http_recorder::warc::WarcWriter::new(io::BufWriter::new(out), gzip)
+        .context("failed to write warcinfo record")?;
+    for entry in reader.entries() {
+        writer
+            .write_entry(&entry.context("failed to read entry")?)
+            .context("failed to write warc records")?;
+    }
+    writer.finish().context("failed to finish warc")?;
+    Ok(())
+}
+
+fn import_har(har: PathBuf, dest: PathBuf, write: WriteArgs) -> anyhow::Result<()> {
+    let options = write.options()?;
+    let file = fs::File::open(&har).context("failed to open har file")?;
+    match http_recorder::har::import(file, &dest, options).context("failed to import har")? {
+        Some(info) => println!("imported {} entries into {}", info.count, dest.display()),
+        None => println!("no entries in {}", har.display()),
+    }
+    Ok(())
+}
+
+fn import_warc(warc: PathBuf, dest: PathBuf, write: WriteArgs) -> anyhow::Result<()> {
+    let options = write.options()?;
+    let file = fs::File::open(&warc).context("failed to open warc file")?;
+    match http_recorder::warc::import(file, &dest, options).context("failed to import warc")? {
+        Some(info) => println!("imported {} entries into {}", info.count, dest.display()),
+        None => println!("no http records in {}", warc.display()),
+    }
+    Ok(())
+}
+
+fn snippet(recording: PathBuf, index: u32, language: Language) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(&recording).context("failed to open recording")?;
+    let entry = reader.get(index).context("failed to read entry")?;
+    print!(
+        "{}",
+        http_recorder::snippet::generate(&entry.request, language)
+    );
+    Ok(())
+}
+
+fn redact(args: RedactArgs) -> anyhow::Result<()> {
+    use http_recorder::{
+        archive::RecordingWriter,
+        redact::{RedactConfig, Redactor},
+    };
+    let redactor = Redactor::new(&RedactConfig {
+        headers: args.headers,
+        cookies: args.cookies,
+        params: args.params,
+        json_paths: args.json_paths,
+    })
+    .context("invalid header name")?;
+    let options = args.write.options()?;
+    let reader = RecordingReader::open(&args.recording).context("failed to open recording")?;
+    let mut writer: Option<RecordingWriter> = None;
+    let mut redacted = 0;
+    for entry in reader.entries() {
+        let mut entry = entry.context("failed to read entry")?;
+        let before = entry.redactions.len();
+        redactor.redact(&mut entry);
+        if entry.redactions.len() > before {
+            redacted += 1;
+        }
+        let w = match &mut writer {
+            Some(w) => w,
+            None => writer.insert(
+                RecordingWriter::with_options(&args.dest, &entry, options.clone())
+                    .context("failed to create recording")?,
+            ),
+        };
+        w.add_entry(&entry).context("failed to write entry")?;
+    }
+    if let Some(w) = writer {
+        let info = w.finish().context("failed to finish recording")?;
+        println!(
+            "redacted {} of {} entries into {}",
+            redacted,
+            info.count,
+            args.dest.display()
+        );
+    }
+    Ok(())
+}
+
+fn import_mitmproxy(args: ImportMitmproxyArgs) -> anyhow::Result<()> {
+    let options = args.write.options()?;
+    for dump in args.dumps {
+        let name = dump
+            .file_stem()
+            .with_context(|| format!("invalid dump file {}", dump.display()))?;
+        let dest = args.dest.join(name);
+        let file =
+            fs::File::open(&dump).with_context(|| format!("failed to open {}", dump.display()))?;
+        match http_recorder::mitmproxy::import(file, &dest, options.clone())
+            .with_context(|| format!("failed to import {}", dump.display()))?
+        {
+            Some(info) => println!(
+                "imported {} entries from {} into {}",
+                info.count,
+                dump.display(),
+                dest.display()
+            ),
+            None => println!("no http flows in {}", dump.display()),
+        }
+    }
+    Ok(())
+}
+
+// Spill directories of a recorder that is still running are picked up as
+// well, so this is meant to be run while mitmproxy is stopped.
+fn recover(args: RecoverArgs) -> anyhow::Result<()> {
+    use http_recorder::spill;
+    let options = args.write.options()?;
+    let spill_dirs = if args.spill_dirs.is_empty() {
+        let tmp_dir = args.tmp_dir.unwrap_or_else(std::env::temp_dir);
+        spill::find_dirs(&tmp_dir)
+            .with_context(|| format!("failed to list {}", tmp_dir.display()))?
+    } else {
+        args.spill_dirs
+    };
+    if spill_dirs.is_empty() {
+        println!("no spill directories found");
+    }
+    for dir in spill_dirs {
+        let name = dir
+            .file_name()
+            .with_context(|| format!("invalid spill directory {}", dir.display()))?;
+        let dest = args.dest.join(name);
+        let recovered = spill::recover(&dir, &dest, options.clone())
+            .with_context(|| format!("failed to recover {}", dir.display()))?;
+        let complete = recovered.skipped.is_empty();
+        for (path, e) in recovered.skipped {
+            eprintln!("skipped {}: {:#}", path.display(), anyhow::Error::from(e));
+        }
+        match &recovered.info {
+            Some(info) => println!(
+                "recovered {} entries ({}-{}) from {} into {}",
+                info.count,
+                info.begin_index,
+                info.end_index,
+                dir.display(),
+                dest.display()
+            ),
+            None => println!("nothing to recover in {}", dir.display()),
+        }
+        if args.remove && complete {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("failed to remove {}", dir.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn tls(recording: PathBuf) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(&recording).context("failed to open recording")?;
+    // (host, version, cipher) -> number of entries
+    let mut seen = std::collections::BTreeMap::<_, u32>::new();
+    for entry in reader.entries() {
+        let entry = entry.context("failed to read entry")?;
+        let host = entry
+            .request
+            .url
+            .host
+            .as_ref()
+            .map_or(String::from("-"), |h| h.to_string());
+        let (version, cipher) = match entry.server_tls {
+            Some(t) => (
+                t.version.unwrap_or_else(|| String::from("?")),
+                t.cipher.unwrap_or_else(|| String::from("?")),
+            ),
+            None => (String::from("-"), String::from("-")),
+        };
+        *seen.entry((host, version, cipher)).or_default() += 1;
+    }
+    let mut out = io::stdout().lock();
+    writeln!(
+        out,
+        "{:<32} {:<8} {:<32} {:>8}",
+        "HOST", "VERSION", "CIPHER", "COUNT"
+    )?;
+    for ((host, version, cipher), count) in seen {
+        writeln!(
+            out,
+            "{:<32} {:<8} {:<32} {:>8}",
+            host, version, cipher, count
+        )?;
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let ret = match Cli::parse().command {
+        Command::Ls { recording } => ls(recording),
+        Command::Show {
+            recording,
+            index,
+            preview,
+        } => show(recording, index, preview),
+        Command::Cat(args) => cat(args),
+        Command::ExportHar { recording, output } => export_har(recording, output),
+        Command::ExportMitmproxy { recording, output } => export_mitmproxy(recording, output),
+        Command::ExportWarc {
+            recording,
+            output,
+            gzip,
+        } => export_warc(recording, output, gzip),
+        Command::ImportHar { har, dest, write } => import_har(har, dest, write),
+        Command::ImportMitmproxy(args) => import_mitmproxy(args),
+        Command::ImportWarc { warc, dest, write } => import_warc(warc, dest, write),
+        Command::Recover(args) => recover(args),
+        Command::Redact(args) => redact(args),
+        Command::Snippet {
+            recording,
+            index,
+            language,
+        } => snippet(recording, index, language),
+        Command::Tls { recording } => tls(recording),
+        Command::Replay {
+            recording,
+            listen,
+            strictness,
+            ignore_host,
+            unmatched_status,
+        } => replay::serve(
+            &recording,
+            listen.as_str(),
+            strictness,
+            ignore_host,
+            unmatched_status,
+        ),
+    };
+    match ret {
+        // output piped into a pager or head that exited early
+        Err(e)
+            if e.chain().any(|c| {
+                c.downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+            }) =>
+        {
+            Ok(())
+        }
+        r => r,
+    }
+}
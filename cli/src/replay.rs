@@ -0,0 +1,310 @@
+use anyhow::Context;
+use http_recorder::{
+    archive::RecordingReader,
+    content::{Digest, Payload},
+    header, request, Request, Response,
+};
+use serde::Serialize;
+use std::{collections::HashMap, io, net::IpAddr, path::Path};
+
+pub const REPORT_PATH: &str = "/_replay/unmatched";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Strictness {
+    /// Method and path
+    Path,
+    /// Method, path and query parameters
+    Query,
+    /// Method, path, query parameters and request body
+    Body,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RouteKey {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum BodyKey {
+    None,
+    Digest(Digest),
+    Form(Vec<(String, String)>),
+    Multipart(Vec<(Option<String>, Digest)>),
+}
+impl BodyKey {
+    fn new(body: &Option<request::Body>) -> Self {
+        match body {
+            None => Self::None,
+            Some(request::Body::Content(c)) => Self::Digest(c.digest.clone()),
+            Some(request::Body::UrlEncodedForm(f)) => Self::Form(
+                f.iter()
+                    .map(|e| (e.name.clone(), e.value.clone()))
+                    .collect(),
+            ),
+            // boundaries differ between requests, so only the parts are compared
+            Some(request::Body::MultipartForm(f)) => Self::Multipart(
+                f.iter()
+                    .map(|e| (e.name.clone(), e.content.digest.clone()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+struct Candidate {
+    index: u32,
+    host: Option<String>,
+    body: BodyKey,
+    response: Response,
+    served: bool,
+}
+
+pub struct Matcher {
+    strictness: Strictness,
+    ignore_host: bool,
+    // host of the listen address
+    local_host: String,
+    routes: HashMap<RouteKey, Vec<Candidate>>,
+}
+impl Matcher {
+    pub fn load(
+        reader: &RecordingReader,
+        strictness: Strictness,
+        ignore_host: bool,
+        addr: &str,
+    ) -> anyhow::Result<Self> {
+        let mut ret = Self {
+            strictness,
+            ignore_host,
+            local_host: addr
+                .rsplit_once(':')
+                .map_or(addr, |(h, _)| h)
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_ascii_lowercase(),
+            routes: HashMap::new(),
+        };
+        for entry in reader.entries() {
+            let entry = entry.context("failed to read entry")?;
+            // failed flows have nothing to replay
+            let response = match entry.response {
+                Some(r) => r,
+                None => continue,
+            };
+            let key = ret.route_key(&entry.request);
+            ret.routes.entry(key).or_default().push(Candidate {
+                index: entry.index,
+                host: host(&entry.request),
+                body: BodyKey::new(&entry.request.body),
+                response,
+                served: false,
+            });
+        }
+        Ok(ret)
+    }
+    fn route_key(&self, request: &Request) -> RouteKey {
+        RouteKey {
+            method: request.method.as_str().to_string(),
+            path: request.url.path.clone(),
+            query: if self.strictness >= Strictness::Query {
+                let mut q: Vec<_> = request
+                    .url
+                    .query
+                    .iter()
+                    .map(|q| (q.name.clone(), q.value.clone()))
+                    .collect();
+                q.sort();
+                q
+            } else {
+                Vec::new()
+            },
+        }
+    }
+    // Clients talking to the replay server directly name it as host, their
+    // requests match recorded ones of any host.
+    fn is_local(&self, host: &str) -> bool {
+        host == "localhost"
+            || host == self.local_host
+            || host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback() || ip.is_unspecified())
+    }
+    // Recorded responses for the same request are replayed in order, the last
+    // one is repeated once all of them have been served.
+    pub fn find(&mut self, request: &Request) -> Option<(u32, &Response)> {
+        let host = host(request).filter(|h| !self.ignore_host && !self.is_local(h));
+        let candidates = self.routes.get_mut(&self.route_key(request))?;
+        let body = BodyKey::new(&request.body);
+        let check_body = self.strictness >= Strictness::Body;
+        let mut last = None;
+        let mut unserved = None;
+        for (i, c) in candidates.iter().enumerate() {
+            if host.is_some() && c.host != host {
+                continue;
+            }
+            if check_body && c.body != body {
+                continue;
+            }
+            last = Some(i);
+            if !c.served {
+                unserved = Some(i);
+                break;
+            }
+        }
+        let c = &mut candidates[unserved.or(last)?];
+        c.served = true;
+        Some((c.index, &c.response))
+    }
+}
+
+fn host(request: &Request) -> Option<String> {
+    request
+        .url
+        .host
+        .as_ref()
+        .map(|h| h.to_string().to_ascii_lowercase())
+}
+
+#[derive(Debug, Serialize)]
+struct Unmatched {
+    method: String,
+    url: String,
+    reason: String,
+}
+
+fn parse_request(request: &mut tiny_http::Request) -> anyhow::Result<Request> {
+    let host = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("host"))
+        .map_or("localhost", |h| h.value.as_str())
+        .to_string();
+    let mut body = Vec::new();
+    io::Read::read_to_end(request.as_reader(), &mut body).context("failed to read body")?;
+    let version = format!("HTTP/{}", request.http_version());
+    // proxy clients send the absolute form
+    let target = request.url();
+    let url = match target.split_once("://") {
+        Some((scheme, _))
+            if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") =>
+        {
+            target.to_string()
+        }
+        _ => format!("http://{}{}", host, target),
+    };
+    Request::parse(
+        version.as_str(),
+        request.method().as_str(),
+        url.as_str(),
+        request
+            .headers()
+            .iter()
+            .map(|h| (h.field.as_str().as_str(), h.value.as_str())),
+        Some(Payload::Raw(body.as_slice())),
+    )
+    .context("failed to parse request")
+}
+
+// hop-by-hop headers and Content-Encoding, unless the body could not be
+// decoded when it was recorded
+fn skip_header(name: &header::HeaderName, decoded: bool) -> bool {
+    (decoded && name == &header::CONTENT_ENCODING)
+        || [
+            header::CONNECTION,
+            header::CONTENT_LENGTH,
+            header::KEEP_ALIVE,
+            header::TRANSFER_ENCODING,
+        ]
+        .contains(name)
+}
+
+fn respond(request: tiny_http::Request, response: &Response) -> io::Result<()> {
+    let data = response
+        .content
+        .as_ref()
+        .and_then(|c| c.data.as_deref())
+        .unwrap_or_default();
+    let decoded = response
+        .content
+        .as_ref()
+        .and_then(|c| c.encoding.as_ref())
+        .is_none_or(|e| e.decoded);
+    let headers = response
+        .headers
+        .0
+        .iter()
+        .filter(|h| !skip_header(&h.name, decoded))
+        .filter_map(|h| {
+            tiny_http::Header::from_bytes(h.name.as_str().as_bytes(), h.value.as_bytes()).ok()
+        })
+        .collect();
+    request.respond(tiny_http::Response::new(
+        tiny_http::StatusCode(response.status_code.0),
+        headers,
+        data,
+        Some(data.len()),
+        None,
+    ))
+}
+
+pub fn serve(
+    recording: &Path,
+    addr: &str,
+    strictness: Strictness,
+    ignore_host: bool,
+    unmatched_status: u16,
+) -> anyhow::Result<()> {
+    let reader = RecordingReader::open(recording).context("failed to open recording")?;
+    let mut matcher = Matcher::load(&reader, strictness, ignore_host, addr)
+        .context("failed to load recording")?;
+    let server = tiny_http::Server::http(addr)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("failed to start server")?;
+    eprintln!(
+        "replaying {} on {}, unmatched requests are listed at {}",
+        recording.display(),
+        addr,
+        REPORT_PATH
+    );
+    let mut unmatched = Vec::new();
+    for mut request in server.incoming_requests() {
+        if request.url() == REPORT_PATH {
+            let report = serde_json::to_vec_pretty(&unmatched).unwrap();
+            if let Err(e) = request.respond(tiny_http::Response::from_data(report)) {
+                eprintln!("failed to send report: {}", e);
+            }
+            continue;
+        }
+        let method = request.method().to_string();
+        let url = request.url().to_string();
+        let reason = match parse_request(&mut request) {
+            Ok(r) => match matcher.find(&r) {
+                Some((index, response)) => {
+                    eprintln!("{} {} -> #{}", method, url, index);
+                    if let Err(e) = respond(request, response) {
+                        eprintln!("failed to send response: {}", e);
+                    }
+                    continue;
+                }
+                None => String::from("no recorded request matched"),
+            },
+            Err(e) => format!("{:#}", e),
+        };
+        eprintln!("{} {} unmatched: {}", method, url, reason);
+        if let Err(e) = request.respond(
+            tiny_http::Response::from_string(reason.as_str()).with_status_code(unmatched_status),
+        ) {
+            eprintln!("failed to send response: {}", e);
+        }
+        unmatched.push(Unmatched {
+            method,
+            url,
+            reason,
+        });
+    }
+    Ok(())
+}
@@ -1,23 +1,49 @@
 import http_recorder
+import logging
 
-from typing import Optional
+from typing import Optional, Sequence
 from mitmproxy import ctx, http, command
 
-ignore_hosts = {
-    "www.googletagmanager.com", "fonts.googleapis.com", "fonts.gstatic.com"
+# option name -> (default, help)
+filter_options = {
+    "record_hosts": ([], "only record hosts matching one of these globs"),
+    "record_ignore_hosts": ([
+        "www.googletagmanager.com", "fonts.googleapis.com", "fonts.gstatic.com"
+    ], "never record hosts matching one of these globs"),
+    "record_path_prefixes":
+    ([], "only record paths starting with one of these"),
+    "record_path_regexes":
+    ([], "only record paths matching one of these regexes"),
+    "record_methods": ([], "only record these request methods"),
+    "record_status":
+    ([], "only record these status codes or ranges, e.g. 200-299"),
+    "record_content_types":
+    ([], "only record responses with a content type matching one of these globs"
+     ),
+    "record_ignore_content_types":
+    ([], "never record responses with a content type matching one of these globs"
+     ),
 }
 
-
-def should_add_flow(flow: http.HTTPFlow) -> bool:
-    if flow.request.host in ignore_hosts:
-        return False
-    return True
+# option name -> help
+redact_options = {
+    "record_redact_headers": "mask the values of these headers",
+    "record_redact_cookies": "mask the values of these cookies",
+    "record_redact_params":
+    "mask these query, urlencoded and multipart form parameters",
+    "record_redact_json_paths":
+    "mask these dotted paths in JSON bodies, * matches any key or index",
+}
 
 
 class HttpRecorder:
 
     def __init__(self):
         self.recorder: http_recorder.Recorder = None
+        # flow id -> body of a streamed response not recorded yet
+        self.streams: dict[str, http_recorder.BodyStream] = {}
+        # flow id -> messages of an open WebSocket connection
+        self.messages: dict[str, http_recorder.MessageStream] = {}
 
     def load(self, loader):
         loader.add_option(
@@ -32,20 +58,213 @@ class HttpRecorder:
             default="record",
             help="name of http record",
         )
+        loader.add_option(
+            name="record_dedup",
+            typespec=bool,
+            default=False,
+            help="store identical bodies only once per recording",
+        )
+        for prefix, help in (("record", "recording"),
+                             ("record_spill", "temporary spill files")):
+            loader.add_option(
+                name=f"{prefix}_codec",
+                typespec=str,
+                default="xz",
+                help=f"compression of the {help}: xz, zstd or none",
+            )
+            loader.add_option(
+                name=f"{prefix}_level",
+                typespec=Optional[int],
+                default=None,
+                help=f"compression level of the {help}, codec default if unset",
+            )
+        loader.add_option(
+            name="record_pack_size",
+            typespec=int,
+            default=512 * 2**20,
+            help="body bytes after which a pack is closed, 0 for no limit",
+        )
+        loader.add_option(
+            name="record_pack_entries",
+            typespec=Optional[int],
+            default=None,
+            help="number of entries after which a pack is closed",
+        )
+        loader.add_option(
+            name="record_pack_interval",
+            typespec=Optional[int],
+            default=None,
+            help="seconds covered by a pack, e.g. 3600 for hourly packs",
+        )
+        loader.add_option(
+            name="record_spill_pack_size",
+            typespec=int,
+            default=256 * 2**20,
+            help="body bytes of a temporary spill pack, kept in memory until packed",
+        )
+        for name, (default, help) in filter_options.items():
+            loader.add_option(
+                name=name,
+                typespec=Sequence[str],
+                default=default,
+                help=help,
+            )
+        for name, help in redact_options.items():
+            loader.add_option(
+                name=name,
+                typespec=Sequence[str],
+                default=[],
+                help=help,
+            )
+        loader.add_option(
+            name="record_stream_event_streams",
+            typespec=bool,
+            default=True,
+            help="stream text/event-stream responses to the client as they arrive",
+        )
+        loader.add_option(
+            name="record_stream_segment_size",
+            typespec=int,
+            default=16 * 2**20,
+            help="body or WebSocket message bytes after which they are recorded as a segment",
+        )
+        loader.add_option(
+            name="record_stream_segment_interval",
+            typespec=Optional[int],
+            default=60,
+            help="seconds after which the body or WebSocket messages received are recorded as a segment",
+        )
+        loader.add_option(
+            name="record_max_body_size",
+            typespec=Optional[int],
+            default=None,
+            help="never record flows with a larger request or response body",
+        )
 
     def configure(self, update):
-        if "record_dest" in update or "record_name" in update:
+        if update & {
+                "record_dest", "record_name", "record_dedup",
+                "record_codec", "record_level", "record_spill_codec",
+                "record_spill_level", "record_pack_size",
+                "record_pack_entries", "record_pack_interval",
+                "record_spill_pack_size", "record_max_body_size",
+                *filter_options, *redact_options
+        }:
+            if self.recorder is not None:
+                self.done()
             self.recorder = http_recorder.Recorder(
                 ctx.options.record_dest,
                 ctx.options.record_name,
+                dedup=ctx.options.record_dedup,
+                codec=ctx.options.record_codec,
+                level=ctx.options.record_level,
+                spill_codec=ctx.options.record_spill_codec,
+                spill_level=ctx.options.record_spill_level,
+                pack_size=ctx.options.record_pack_size or None,
+                pack_entries=ctx.options.record_pack_entries,
+                pack_interval=ctx.options.record_pack_interval,
+                spill_pack_size=ctx.options.record_spill_pack_size,
+                filter=http_recorder.Filter(
+                    hosts=ctx.options.record_hosts,
+                    ignore_hosts=ctx.options.record_ignore_hosts,
+                    path_prefixes=ctx.options.record_path_prefixes,
+                    path_regexes=ctx.options.record_path_regexes,
+                    methods=ctx.options.record_methods,
+                    status=ctx.options.record_status,
+                    content_types=ctx.options.record_content_types,
+                    ignore_content_types=ctx.options.
+                    record_ignore_content_types,
+                    max_body_size=ctx.options.record_max_body_size,
+                ),
+                redact=http_recorder.Redact(
+                    headers=ctx.options.record_redact_headers,
+                    cookies=ctx.options.record_redact_cookies,
+                    params=ctx.options.record_redact_params,
+                    json_paths=ctx.options.record_redact_json_paths,
+                ),
             )
 
     def done(self):
-        self.recorder.finish()
+        try:
+            self.recorder.finish()
+        except http_recorder.RecorderError as e:
+            logging.error("failed to finish recording: %s", e)
+
+    def record(self, flow: http.HTTPFlow, add):
+        # the recorder keeps going, or restarts itself, after an error
+        try:
+            add()
+        except http_recorder.ParseError as e:
+            logging.warning("not recording %s: %s", flow.request.pretty_url,
+                            e)
+        except http_recorder.RecorderError as e:
+            logging.error("failed to record %s: %s", flow.request.pretty_url,
+                          e)
+
+    def add_flow(self, flow: http.HTTPFlow):
+        # a flow can fail after its response hook already ran
+        if not flow.metadata.get("http_recorder_saved"):
+            flow.metadata["http_recorder_saved"] = True
+            stream = self.streams.pop(flow.id, None)
+            messages = self.messages.pop(flow.id, None)
+            self.record(
+                flow, lambda: self.recorder.add_flow(flow, stream, messages))
+
+    def responseheaders(self, flow: http.HTTPFlow):
+        # mitmproxy drops streamed bodies, buffer them in a BodyStream
+        # instead, unless another addon already consumes the stream. Long-lived
+        # streams are recorded in segments while they are open, the rest once
+        # the response completes
+        stream = flow.response.stream
+        event_stream = flow.response.headers.get(
+            "content-type", "").startswith("text/event-stream")
+        if stream is True or (not stream
+                              and ctx.options.record_stream_event_streams
+                              and event_stream):
+            body = http_recorder.BodyStream(
+                segment_size=ctx.options.record_stream_segment_size,
+                segment_interval=ctx.options.record_stream_segment_interval,
+                # an encoded body cannot be cut at event boundaries
+                event_stream=event_stream
+                and "content-encoding" not in flow.response.headers,
+            )
+            self.streams[flow.id] = body
+
+            def write(chunk: bytes) -> bytes:
+                body.write(chunk)
+                if body.segment_due():
+                    self.record(
+                        flow, lambda: self.recorder.add_segment(flow, body))
+                return chunk
+
+            flow.response.stream = write
 
     def response(self, flow: http.HTTPFlow):
-        if flow.error == None and should_add_flow(flow):
-            self.recorder.add_flow(flow)
+        # upgraded flows are saved with their messages, see websocket_message
+        if flow.error is None and flow.websocket is None:
+            self.add_flow(flow)
+
+    def error(self, flow: http.HTTPFlow):
+        if flow.websocket is None:
+            self.add_flow(flow)
+
+    def websocket_message(self, flow: http.HTTPFlow):
+        # messages of long-lived connections are recorded in segments while
+        # they are open, the rest once the connection is closed
+        messages = self.messages.get(flow.id)
+        if messages is None:
+            messages = http_recorder.MessageStream(
+                segment_size=ctx.options.record_stream_segment_size,
+                segment_interval=ctx.options.record_stream_segment_interval,
+            )
+            self.messages[flow.id] = messages
+        messages.add(len(flow.websocket.messages[-1].content))
+        if messages.segment_due():
+            self.record(flow,
+                        lambda: self.recorder.add_messages(flow, messages))
+
+    def websocket_end(self, flow: http.HTTPFlow):
+        self.add_flow(flow)
 
 
-addons = [HttpRecorder()]
\ No newline at end of file
+addons = [HttpRecorder()]
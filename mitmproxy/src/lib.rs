@@ -1,9 +1,21 @@
 use anyhow::Context;
-use pyo3::{pyclass, pymethods, pymodule, FromPyObject};
+use http_recorder::{
+    archive::{
+        writer::{Rotation, WriteOptions, MAX_PACK},
+        Codec, Compression,
+    },
+    filter::{Filter, FilterConfig},
+    mitmproxy::flow,
+    redact::{RedactConfig, Redactor},
+    response::Segment,
+};
+use pyo3::{pyclass, pymethods, pymodule, FromPyObject, PyRefMut};
 use std::{
+    borrow::Cow,
     fs,
     path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 #[derive(FromPyObject)]
@@ -13,42 +25,175 @@ struct Headers<'a> {
 #[derive(FromPyObject)]
 struct Request<'a> {
     timestamp_start: f64,
+    timestamp_end: Option<f64>,
     http_version: &'a str,
     method: &'a str,
     url: &'a str,
     headers: Headers<'a>,
-    content: Option<&'a [u8]>,
+    raw_content: Option<&'a [u8]>,
 }
 impl<'a> Request<'a> {
-    fn into_request(self) -> anyhow::Result<http_recorder::Request> {
-        http_recorder::Request::parse(
-            self.http_version,
-            self.method,
-            self.url,
-            self.headers.fields.into_iter(),
-            self.content,
-        )
-        .context("failed to parse request")
+    fn into_request(self) -> flow::Request<'a> {
+        flow::Request {
+            timestamp_start: self.timestamp_start,
+            timestamp_end: self.timestamp_end,
+            http_version: self.http_version,
+            method: self.method,
+            url: Cow::Borrowed(self.url),
+            headers: self.headers.fields,
+            raw_content: self.raw_content,
+        }
+    }
+}
+/// Default body bytes after which a streamed body is recorded as a segment
+pub const SEGMENT_SIZE: usize = 16 * (1 << 20); // 16 MiB
+
+/// Buffers a streamed body, which mitmproxy does not keep. The addon passes
+/// it to `Recorder.add_segment` whenever `segment_due`, which records the
+/// body received so far in an entry of its own, so that a long-lived stream
+/// is recorded while it is open and never held in memory as a whole. The
+/// rest is recorded with the flow once the response completes.
+#[pyclass]
+struct BodyStream {
+    data: Vec<u8>,
+    segment_size: usize,
+    segment_interval: Option<Duration>,
+    /// of the current segment
+    started: Instant,
+    /// segments of an event stream end with a complete event
+    event_stream: bool,
+    /// index of the entry with the first segment, once one was recorded
+    first: Option<u32>,
+    /// of `data` in the body
+    offset: u64,
+}
+
+#[pymethods]
+impl BodyStream {
+    #[new]
+    #[pyo3(signature = (*, segment_size = SEGMENT_SIZE, segment_interval = None, event_stream = false))]
+    pub fn new(segment_size: usize, segment_interval: Option<u64>, event_stream: bool) -> Self {
+        Self {
+            data: Vec::new(),
+            segment_size,
+            segment_interval: segment_interval.map(Duration::from_secs),
+            started: Instant::now(),
+            event_stream,
+            first: None,
+            offset: 0,
+        }
+    }
+    pub fn write(&mut self, chunk: &[u8]) {
+        self.data.extend_from_slice(chunk);
+    }
+    /// Whether the body received so far should be recorded as a segment.
+    pub fn segment_due(&self) -> bool {
+        self.data.len() >= self.segment_size
+            || !self.data.is_empty()
+                && self
+                    .segment_interval
+                    .is_some_and(|i| self.started.elapsed() >= i)
+    }
+}
+impl BodyStream {
+    // Takes the body of the next segment and its offset: all of it for the
+    // last one, otherwise up to the last complete event of an event stream,
+    // which is cut anywhere only once it reaches the segment size.
+    fn take_segment(&mut self, last: bool) -> (Vec<u8>, u64) {
+        let end = match last || !self.event_stream {
+            true => self.data.len(),
+            false => match http_recorder::sse::complete_len(&self.data) {
+                Some(end) => end,
+                None if self.data.len() >= self.segment_size => self.data.len(),
+                None => 0,
+            },
+        };
+        let rest = self.data.split_off(end);
+        let data = std::mem::replace(&mut self.data, rest);
+        let offset = self.offset;
+        if !data.is_empty() {
+            self.offset += data.len() as u64;
+            self.started = Instant::now();
+        }
+        (data, offset)
     }
 }
+
+/// Counts the messages of a WebSocket connection, which are recorded with the
+/// flow once it closes. The addon passes it to `Recorder.add_messages`
+/// whenever `segment_due`, which records the messages received since the
+/// last segment in an entry of its own, so that those of a connection that
+/// stays open, or is never closed, are recorded too.
+#[pyclass]
+struct MessageStream {
+    /// messages received
+    received: usize,
+    /// messages recorded in earlier segments
+    recorded: usize,
+    /// payload bytes of the messages received since
+    size: usize,
+    segment_size: usize,
+    segment_interval: Option<Duration>,
+    /// of the current segment
+    started: Instant,
+    /// index of the entry with the first segment, once one was recorded
+    first: Option<u32>,
+}
+
+#[pymethods]
+impl MessageStream {
+    #[new]
+    #[pyo3(signature = (*, segment_size = SEGMENT_SIZE, segment_interval = None))]
+    pub fn new(segment_size: usize, segment_interval: Option<u64>) -> Self {
+        Self {
+            received: 0,
+            recorded: 0,
+            size: 0,
+            segment_size,
+            segment_interval: segment_interval.map(Duration::from_secs),
+            started: Instant::now(),
+            first: None,
+        }
+    }
+    /// Counts a message with a payload of `size` bytes.
+    pub fn add(&mut self, size: usize) {
+        self.received += 1;
+        self.size += size;
+    }
+    /// Whether the messages received since the last segment should be
+    /// recorded as one.
+    pub fn segment_due(&self) -> bool {
+        self.size >= self.segment_size
+            || self.received > self.recorded
+                && self
+                    .segment_interval
+                    .is_some_and(|i| self.started.elapsed() >= i)
+    }
+}
+
 #[derive(FromPyObject)]
 struct Response<'a> {
-    timestamp_end: f64,
+    timestamp_start: f64,
+    timestamp_end: Option<f64>,
     http_version: &'a str,
     status_code: u16,
     headers: Headers<'a>,
-    content: Option<&'a [u8]>,
+    raw_content: Option<&'a [u8]>,
 }
 impl<'a> Response<'a> {
-    fn into_response(self, url: &str) -> anyhow::Result<http_recorder::Response> {
-        http_recorder::Response::parse(
-            self.http_version,
-            self.status_code,
-            url,
-            self.headers.fields.into_iter(),
-            self.content,
-        )
-        .context("failed to parse response")
+    /// `streamed` replaces the content, which mitmproxy drops when streaming.
+    fn into_response(self, streamed: Option<Vec<u8>>) -> flow::Response<'a> {
+        flow::Response {
+            timestamp_start: self.timestamp_start,
+            timestamp_end: self.timestamp_end,
+            http_version: self.http_version,
+            status_code: self.status_code,
+            headers: self.headers.fields,
+            raw_content: match streamed {
+                Some(s) => Some(Cow::Owned(s)),
+                None => self.raw_content.map(Cow::Borrowed),
+            },
+        }
     }
 }
 
@@ -58,15 +203,11 @@ enum Addr<'a> {
     Long((&'a str, u16, &'a pyo3::PyAny, &'a pyo3::PyAny)),
 }
 impl<'a> Addr<'a> {
-    fn to_addr(&self) -> anyhow::Result<std::net::SocketAddr> {
-        let (a, p) = match self {
+    fn to_address(&self) -> flow::Address<'a> {
+        match self {
             Self::Short(ap) => *ap,
             Self::Long((a, p, _, _)) => (*a, *p),
-        };
-        Ok(std::net::SocketAddr::new(
-            a.parse().context("failed to parse address")?,
-            p,
-        ))
+        }
     }
 }
 
@@ -77,153 +218,639 @@ struct Client<'a> {
 #[derive(FromPyObject)]
 struct Server<'a> {
     peername: Option<Addr<'a>>,
+    timestamp_start: Option<f64>,
+    timestamp_tcp_setup: Option<f64>,
+    timestamp_tls_setup: Option<f64>,
+}
+struct Certificate(http_recorder::tls::Certificate);
+impl<'a> FromPyObject<'a> for Certificate {
+    fn extract(cert: &'a pyo3::PyAny) -> pyo3::PyResult<Self> {
+        let time = |name: &str| -> pyo3::PyResult<_> {
+            Ok(flow::timestamp(
+                cert.getattr(name)?.call_method0("timestamp")?.extract()?,
+            ))
+        };
+        let mut alt_names = Vec::new();
+        for name in cert.getattr("altnames")?.iter()? {
+            let name = name?;
+            // older mitmproxy versions return strings, newer ones
+            // cryptography's GeneralName
+            alt_names.push(match name.extract() {
+                Ok(n) => n,
+                Err(_) => name.getattr("value")?.str()?.to_string(),
+            });
+        }
+        let fingerprint: &[u8] = cert.call_method0("fingerprint")?.extract()?;
+        Ok(Self(http_recorder::tls::Certificate {
+            subject: cert.getattr("subject")?.extract()?,
+            issuer: cert.getattr("issuer")?.extract()?,
+            alt_names,
+            not_before: time("notbefore")?,
+            not_after: time("notafter")?,
+            serial: cert
+                .getattr("serial")?
+                .call_method1("__format__", ("x",))?
+                .extract()?,
+            fingerprint: http_recorder::content::SHA256Digest(fingerprint.try_into().map_err(
+                |_| pyo3::exceptions::PyValueError::new_err("fingerprint is not a SHA-256 digest"),
+            )?),
+        }))
+    }
+}
+/// The TLS attributes shared by mitmproxy's client and server connections.
+#[derive(FromPyObject)]
+struct Tls<'a> {
+    tls_established: bool,
+    tls_version: Option<&'a str>,
+    cipher: Option<&'a str>,
+    alpn: Option<&'a [u8]>,
+    sni: Option<&'a str>,
+    certificate_list: Vec<Certificate>,
+}
+impl<'a> Tls<'a> {
+    fn into_tls(self) -> flow::Tls<'a> {
+        flow::Tls {
+            established: self.tls_established,
+            version: self.tls_version,
+            cipher: self.cipher,
+            alpn: self.alpn,
+            sni: self.sni,
+            certificates: self.certificate_list.into_iter().map(|c| c.0).collect(),
+        }
+    }
+}
+
+#[derive(FromPyObject)]
+struct WebSocketMessage<'a> {
+    from_client: bool,
+    #[pyo3(attribute("type"))]
+    opcode: u8,
+    content: &'a [u8],
+    timestamp: f64,
+}
+#[derive(FromPyObject)]
+struct WebSocketData<'a> {
+    messages: Vec<WebSocketMessage<'a>>,
+    closed_by_client: Option<bool>,
+    close_code: Option<u16>,
+    close_reason: Option<&'a str>,
+}
+impl<'a> WebSocketData<'a> {
+    fn into_websocket(self) -> flow::WebSocketData<'a> {
+        flow::WebSocketData {
+            messages: self
+                .messages
+                .into_iter()
+                .map(|m| flow::WebSocketMessage {
+                    from_client: m.from_client,
+                    opcode: m.opcode,
+                    content: m.content,
+                    timestamp: m.timestamp,
+                })
+                .collect(),
+            closed_by_client: self.closed_by_client,
+            close_code: self.close_code,
+            close_reason: self.close_reason,
+        }
+    }
+}
+
+#[derive(FromPyObject)]
+struct Error<'a> {
+    msg: &'a str,
+    timestamp: f64,
 }
 #[derive(FromPyObject)]
 pub struct Flow<'a> {
     client_conn: Client<'a>,
     server_conn: Server<'a>,
+    #[pyo3(attribute("client_conn"))]
+    client_tls: Tls<'a>,
+    #[pyo3(attribute("server_conn"))]
+    server_tls: Tls<'a>,
     request: Request<'a>,
-    response: Response<'a>,
+    response: Option<Response<'a>>,
+    error: Option<Error<'a>>,
+    websocket: Option<WebSocketData<'a>>,
 }
+
 impl<'a> Flow<'a> {
-    fn into_entry(self, index: u32) -> anyhow::Result<http_recorder::Entry> {
-        use chrono::TimeZone;
-        let utc = chrono::Utc;
-        Ok(http_recorder::Entry {
-            version: http_recorder::VERSION,
-            index,
-            client_addr: self.client_conn.peername.to_addr()?,
-            server_addr: match self.server_conn.peername {
-                Some(a) => Some(a.to_addr()?),
-                None => None,
+    fn into_flow(self, streamed: Option<Vec<u8>>) -> flow::Flow<'a> {
+        flow::Flow {
+            client_conn: flow::Client {
+                peername: self.client_conn.peername.to_address(),
+                tls: self.client_tls.into_tls(),
             },
-            timings: http_recorder::Timings {
-                start_time: utc
-                    .timestamp_nanos((self.request.timestamp_start * 1_000_000_000_f64) as i64),
-                finish_time: utc
-                    .timestamp_nanos((self.response.timestamp_end * 1_000_000_000_f64) as i64),
+            server_conn: flow::Server {
+                peername: self.server_conn.peername.map(|a| a.to_address()),
+                timestamp_start: self.server_conn.timestamp_start,
+                timestamp_tcp_setup: self.server_conn.timestamp_tcp_setup,
+                timestamp_tls_setup: self.server_conn.timestamp_tls_setup,
+                tls: self.server_tls.into_tls(),
             },
-            response: self.response.into_response(self.request.url)?,
-            request: self.request.into_request()?,
-        })
+            request: self.request.into_request(),
+            response: self.response.map(|r| r.into_response(streamed)),
+            error: self.error.map(|e| flow::Error {
+                msg: e.msg,
+                timestamp: e.timestamp,
+            }),
+            websocket: self.websocket.map(WebSocketData::into_websocket),
+        }
+    }
+}
+
+// Entries dropped by the filter are never redacted nor saved.
+fn filtered_entry(
+    flow: Flow<'_>,
+    streamed: Option<Vec<u8>>,
+    segment: Option<Segment>,
+    index: u32,
+    filter: &Filter,
+    redactor: &Redactor,
+) -> anyhow::Result<Option<http_recorder::Entry>> {
+    let mut entry = flow.into_flow(streamed).into_entry(index)?;
+    if let Some(r) = &mut entry.response {
+        r.segment = segment;
+    }
+    if !filter.matches(&entry) {
+        return Ok(None);
     }
+    redactor.redact(&mut entry);
+    Ok(Some(entry))
 }
 
+mod reader;
 mod tar_saver;
 mod tmp_saver;
 
+// pyo3 0.18 expands create_exception! to a cfg that newer compilers warn about
+#[allow(unexpected_cfgs)]
+mod exceptions {
+    use pyo3::exceptions::PyException;
+
+    pyo3::create_exception!(
+        http_recorder,
+        RecorderError,
+        PyException,
+        "A flow was not recorded."
+    );
+    pyo3::create_exception!(
+        http_recorder,
+        ParseError,
+        RecorderError,
+        "A flow could not be converted into an entry."
+    );
+    pyo3::create_exception!(
+        http_recorder,
+        SaveError,
+        RecorderError,
+        "An entry could not be written to the spill directory."
+    );
+    pyo3::create_exception!(
+        http_recorder,
+        SaverError,
+        RecorderError,
+        "The savers failed and could not be restarted, or failed to finish the recording."
+    );
+}
+use exceptions::{ParseError, RecorderError, SaveError, SaverError};
+
+/// How long flows are dropped after the savers could not be restarted
+/// before trying again.
+const RESTART_DELAY: Duration = Duration::from_secs(30);
+
+struct Config {
+    dest: PathBuf,
+    name: String,
+    options: WriteOptions,
+    spill_compression: Compression,
+    spill_pack_size: u64,
+}
+
 struct InnerRecorder {
-    index: u32,
     tmp_saver: tmp_saver::TmpSaver,
     dest_saver: tar_saver::DestSaverHandle,
 }
-enum AddFlowError {
-    ParseError(anyhow::Error),
-    SaveError(anyhow::Error),
+enum SaveEntryError {
+    Io(anyhow::Error),
     SaverFailed,
 }
 impl InnerRecorder {
-    fn new<P: AsRef<Path>>(dest: P, name: &str, flow: Flow<'_>) -> anyhow::Result<Self> {
-        let entry = flow.into_entry(0)?;
+    /// Starts the savers, continuing `recording` in a new pack if set.
+    fn start(
+        config: &Config,
+        recording: Option<&Path>,
+        entry: &http_recorder::Entry,
+    ) -> anyhow::Result<Self> {
         let (tmp_core, dest_core) = {
             let cores = core_affinity::get_core_ids().unwrap_or_default();
-            if cores.len() < 3 {
-                log::warn!("too few cpu cores: {}, at lease 3 recommanded", cores.len());
+            let xz = config.options.compression.codec == Codec::Xz
+                || config.spill_compression.codec == Codec::Xz;
+            if cores.len() < 3 && xz {
+                log::warn!(
+                    "too few cpu cores for xz: {}, at lease 3 recommanded, or use zstd",
+                    cores.len()
+                );
             }
-            (cores.get(0).copied(), cores.get(1).copied())
+            (cores.first().copied(), cores.get(1).copied())
         };
-        let mut ret = Self {
-            index: 0,
-            tmp_saver: tmp_saver::TmpSaver::new(tmp_core, &entry)
-                .context("failed to start tmp saver")?,
-            dest_saver: tar_saver::DestSaver::start(dest, name, dest_core, &entry)
-                .context("failed to start tar saver")?,
+        let options = config.options.clone();
+        let tmp_saver = tmp_saver::TmpSaver::new(
+            tmp_core,
+            entry,
+            config.spill_compression,
+            config.spill_pack_size,
+        )
+        .context("failed to start tmp saver")?;
+        let dest_saver = match recording {
+            Some(r) => tar_saver::DestSaver::resume(r, dest_core, entry, options),
+            None => tar_saver::DestSaver::start(
+                &config.dest,
+                config.name.as_str(),
+                dest_core,
+                entry,
+                options,
+            ),
         };
-        match ret.save_entry(Arc::new(entry)) {
-            Ok(()) => Ok(ret),
-            Err(e) => Err(match e {
-                AddFlowError::ParseError(e) => e.context("failed to parse flow"),
-                AddFlowError::SaveError(e) => e,
-                AddFlowError::SaverFailed => {
-                    log::error!("saver failed {:?}", ret.finish().unwrap_err());
-                    std::process::abort();
-                }
+        match dest_saver {
+            Ok(dest_saver) => Ok(Self {
+                tmp_saver,
+                dest_saver,
             }),
+            Err(e) => {
+                if let Err(e) = tmp_saver
+                    .finish()
+                    .and_then(|p| fs::remove_dir_all(p).context("failed to remove tmp dir"))
+                {
+                    log::error!("failed to stop tmp saver: {:?}", e);
+                }
+                Err(e.context("failed to start tar saver"))
+            }
         }
     }
-    fn save_entry(&mut self, entry: Arc<http_recorder::Entry>) -> Result<(), AddFlowError> {
+    fn save_entry(&mut self, entry: Arc<http_recorder::Entry>) -> Result<(), SaveEntryError> {
         if let Err(e) = self.tmp_saver.add_entry(Arc::clone(&entry)) {
             return Err(match e {
-                tmp_saver::AddEntryError::Io(e) => AddFlowError::SaveError(
+                tmp_saver::AddEntryError::Io(e) => SaveEntryError::Io(
                     anyhow::Error::new(e).context("failed to save entry to tmpdir"),
                 ),
-                tmp_saver::AddEntryError::Packer => AddFlowError::SaverFailed,
+                tmp_saver::AddEntryError::Packer => SaveEntryError::SaverFailed,
             });
         }
         self.dest_saver
             .sender
             .send(entry)
-            .map_err(|_| AddFlowError::SaverFailed)
-    }
-    fn add_flow(&mut self, flow: Flow<'_>) -> Result<(), AddFlowError> {
-        self.save_entry(Arc::new(
-            flow.into_entry(self.index)
-                .map_err(AddFlowError::ParseError)?,
-        ))?;
-        self.index += 1;
-        Ok(())
+            .map_err(|_| SaveEntryError::SaverFailed)
     }
-    fn finish(self) -> anyhow::Result<PathBuf> {
-        self.dest_saver
+    /// Finishes both savers. The spill directory is only removed if both
+    /// succeeded, otherwise it is kept for `http-recorder recover`.
+    fn finish(self) -> anyhow::Result<()> {
+        let spill_dir = self.tmp_saver.path().to_path_buf();
+        let dest = self
+            .dest_saver
             .finish()
-            .context("failed to finish dest saver")?;
-        self.tmp_saver.finish()
+            .context("failed to finish dest saver");
+        let tmp = self.tmp_saver.finish();
+        match dest.and(tmp) {
+            Ok(p) => fs::remove_dir_all(p).context("failed to remove tmp dir"),
+            Err(e) => {
+                log::error!(
+                    "keeping spill directory {} for recovery",
+                    spill_dir.display()
+                );
+                Err(e)
+            }
+        }
+    }
+}
+
+#[pyclass(name = "Filter")]
+struct FlowFilter {
+    filter: Filter,
+}
+
+#[pymethods]
+impl FlowFilter {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        hosts = Vec::new(),
+        ignore_hosts = Vec::new(),
+        path_prefixes = Vec::new(),
+        path_regexes = Vec::new(),
+        methods = Vec::new(),
+        status = Vec::new(),
+        content_types = Vec::new(),
+        ignore_content_types = Vec::new(),
+        max_body_size = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        hosts: Vec<String>,
+        ignore_hosts: Vec<String>,
+        path_prefixes: Vec<String>,
+        path_regexes: Vec<String>,
+        methods: Vec<String>,
+        status: Vec<String>,
+        content_types: Vec<String>,
+        ignore_content_types: Vec<String>,
+        max_body_size: Option<u64>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            filter: Filter::new(&FilterConfig {
+                hosts,
+                ignore_hosts,
+                path_prefixes,
+                path_regexes,
+                methods,
+                status,
+                content_types,
+                ignore_content_types,
+                max_body_size,
+            })
+            .context("invalid filter")?,
+        })
+    }
+}
+
+#[pyclass(name = "Redact")]
+struct FlowRedactor {
+    redactor: Redactor,
+}
+
+#[pymethods]
+impl FlowRedactor {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        headers = Vec::new(),
+        cookies = Vec::new(),
+        params = Vec::new(),
+        json_paths = Vec::new(),
+    ))]
+    pub fn new(
+        headers: Vec<String>,
+        cookies: Vec<String>,
+        params: Vec<String>,
+        json_paths: Vec<String>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            redactor: Redactor::new(&RedactConfig {
+                headers,
+                cookies,
+                params,
+                json_paths,
+            })
+            .context("invalid redaction")?,
+        })
     }
 }
 
 #[pyclass]
 struct Recorder {
-    dest: PathBuf,
-    name: String,
+    config: Config,
+    filter: Filter,
+    redactor: Redactor,
+    /// of the next entry
+    index: u32,
+    /// once started, continued in a new pack when the savers are restarted
+    recording: Option<PathBuf>,
     inner: Option<InnerRecorder>,
+    /// set while flows are dropped because the savers failed to restart
+    degraded_since: Option<Instant>,
+}
+impl Recorder {
+    fn add(
+        &mut self,
+        mut flow: Flow<'_>,
+        mut stream: Option<PyRefMut<'_, BodyStream>>,
+        mut messages: Option<PyRefMut<'_, MessageStream>>,
+        complete: bool,
+    ) -> pyo3::PyResult<()> {
+        let index = self.index;
+        let mut message_segment = None;
+        if let (Some(m), Some(ws)) = (&mut messages, &mut flow.websocket) {
+            let received = ws.messages.len();
+            if !complete && received <= m.recorded {
+                return Ok(());
+            }
+            // a connection recorded in one piece is no segment
+            message_segment = (!complete || m.first.is_some()).then(|| Segment {
+                first: m.first.unwrap_or(index),
+                offset: m.recorded as u64,
+                last: complete,
+            });
+            ws.messages.drain(..m.recorded.min(received));
+            m.recorded = received;
+            m.size = 0;
+            m.started = Instant::now();
+        }
+        let (streamed, segment) = match &mut stream {
+            Some(s) => {
+                let (data, offset) = s.take_segment(complete);
+                if data.is_empty() && !complete {
+                    return Ok(());
+                }
+                // a body recorded in one piece is no segment
+                let segment = (!complete || s.first.is_some()).then(|| Segment {
+                    first: s.first.unwrap_or(index),
+                    offset,
+                    last: complete,
+                });
+                (Some(data), segment)
+            }
+            None => (None, None),
+        };
+        match filtered_entry(flow, streamed, segment, index, &self.filter, &self.redactor) {
+            Ok(Some(mut entry)) => {
+                if let Some(ws) = &mut entry.websocket {
+                    ws.segment = message_segment;
+                }
+                self.save_entry(entry)?
+            }
+            Ok(None) => return Ok(()),
+            Err(e) => return Err(ParseError::new_err(format!("{:#}", e))),
+        }
+        if let Some(s) = &mut stream {
+            s.first.get_or_insert(index);
+        }
+        if let Some(m) = &mut messages {
+            m.first.get_or_insert(index);
+        }
+        Ok(())
+    }
+    fn save_entry(&mut self, entry: http_recorder::Entry) -> pyo3::PyResult<()> {
+        if self
+            .degraded_since
+            .is_some_and(|t| t.elapsed() < RESTART_DELAY)
+        {
+            log::warn!(
+                "recorder degraded, dropped {} {}",
+                entry.request.method.as_str(),
+                entry.request.url.url
+            );
+            return Ok(());
+        }
+        let entry = Arc::new(entry);
+        if let Some(inner) = &mut self.inner {
+            match inner.save_entry(Arc::clone(&entry)) {
+                Ok(()) => {
+                    self.index += 1;
+                    return Ok(());
+                }
+                Err(SaveEntryError::Io(e)) => return Err(SaveError::new_err(format!("{:#}", e))),
+                Err(SaveEntryError::SaverFailed) => {
+                    let inner = self.inner.take().unwrap();
+                    if let Err(e) = inner.finish() {
+                        log::error!("saver failed, restarting: {:?}", e);
+                    }
+                }
+            }
+        }
+        self.restart(entry)
+    }
+    // Starts the savers with `entry`, in a new pack of the recording if one
+    // was started before.
+    fn restart(&mut self, entry: Arc<http_recorder::Entry>) -> pyo3::PyResult<()> {
+        let started = InnerRecorder::start(&self.config, self.recording.as_deref(), &entry)
+            .and_then(|mut inner| match inner.save_entry(entry) {
+                Ok(()) => Ok(inner),
+                Err(SaveEntryError::Io(e)) => Err(e),
+                Err(SaveEntryError::SaverFailed) => Err(inner
+                    .finish()
+                    .err()
+                    .unwrap_or_else(|| anyhow::anyhow!("saver stopped"))),
+            });
+        match started {
+            Ok(inner) => {
+                self.recording = Some(inner.dest_saver.path.clone());
+                self.inner = Some(inner);
+                self.degraded_since = None;
+                self.index += 1;
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(
+                    "failed to start saver, dropping flows for {}s: {:?}",
+                    RESTART_DELAY.as_secs(),
+                    e
+                );
+                self.degraded_since = Some(Instant::now());
+                Err(SaverError::new_err(format!("{:#}", e)))
+            }
+        }
+    }
 }
 
 #[pymethods]
 impl Recorder {
     #[new]
-    pub fn new(dest: &str, name: &str) -> anyhow::Result<Self> {
+    #[pyo3(signature = (
+        dest,
+        name,
+        dedup = false,
+        filter = None,
+        redact = None,
+        codec = "xz",
+        level = None,
+        spill_codec = "xz",
+        spill_level = None,
+        pack_size = MAX_PACK,
+        pack_entries = None,
+        pack_interval = None,
+        spill_pack_size = tmp_saver::TMP_PACK_SIZE,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        dest: &str,
+        name: &str,
+        dedup: bool,
+        filter: Option<&FlowFilter>,
+        redact: Option<&FlowRedactor>,
+        codec: &str,
+        level: Option<i32>,
+        spill_codec: &str,
+        spill_level: Option<i32>,
+        pack_size: Option<u64>,
+        pack_entries: Option<u32>,
+        pack_interval: Option<u64>,
+        spill_pack_size: u64,
+    ) -> anyhow::Result<Self> {
+        let compression = |codec: &str, level| -> anyhow::Result<_> {
+            Ok(Compression::new(codec.parse()?, level)?)
+        };
         Ok(Self {
-            dest: PathBuf::from(dest),
-            name: name.to_owned(),
+            config: Config {
+                dest: PathBuf::from(dest),
+                name: name.to_owned(),
+                options: WriteOptions {
+                    dedup,
+                    compression: compression(codec, level).context("invalid compression")?,
+                    rotation: Rotation {
+                        max_size: pack_size,
+                        max_entries: pack_entries,
+                        interval: pack_interval.map(Duration::from_secs),
+                    },
+                },
+                spill_compression: compression(spill_codec, spill_level)
+                    .context("invalid spill compression")?,
+                spill_pack_size,
+            },
+            filter: match filter {
+                Some(f) => f.filter.clone(),
+                None => Filter::new(&FilterConfig::default()).unwrap(),
+            },
+            redactor: match redact {
+                Some(r) => r.redactor.clone(),
+                None => Redactor::new(&RedactConfig::default()).unwrap(),
+            },
+            index: 0,
+            recording: None,
             inner: None,
+            degraded_since: None,
         })
     }
-    pub fn add_flow(&mut self, flow: Flow<'_>) -> anyhow::Result<()> {
-        match &mut self.inner {
-            Some(i) => match i.add_flow(flow) {
-                Ok(()) => Ok(()),
-                Err(AddFlowError::ParseError(p)) => Err(p),
-                Err(AddFlowError::SaveError(e)) => {
-                    log::error!("{:?}", e);
-                    std::process::abort()
-                }
-                Err(AddFlowError::SaverFailed) => self.finish(),
-            },
-            None => {
-                self.inner = Some(InnerRecorder::new(&self.dest, self.name.as_str(), flow)?);
-                Ok(())
-            }
-        }
+    /// Raises a `RecorderError` if the flow was not recorded. `stream` holds
+    /// the body of a streamed response, or what is left of it, `messages`
+    /// counts the messages of a WebSocket connection recorded in segments
+    /// before.
+    #[pyo3(signature = (flow, stream = None, messages = None))]
+    pub fn add_flow(
+        &mut self,
+        flow: Flow<'_>,
+        stream: Option<PyRefMut<'_, BodyStream>>,
+        messages: Option<PyRefMut<'_, MessageStream>>,
+    ) -> pyo3::PyResult<()> {
+        self.add(flow, stream, messages, true)
     }
-    pub fn finish(&mut self) -> anyhow::Result<()> {
+    /// Records the body `stream` received so far as a segment, in an entry of
+    /// its own. Raises a `RecorderError` if it was not recorded.
+    pub fn add_segment(
+        &mut self,
+        flow: Flow<'_>,
+        stream: PyRefMut<'_, BodyStream>,
+    ) -> pyo3::PyResult<()> {
+        self.add(flow, Some(stream), None, false)
+    }
+    /// Records the WebSocket messages of the flow received since the last
+    /// segment as a segment, in an entry of its own. Raises a `RecorderError`
+    /// if they were not recorded.
+    pub fn add_messages(
+        &mut self,
+        flow: Flow<'_>,
+        messages: PyRefMut<'_, MessageStream>,
+    ) -> pyo3::PyResult<()> {
+        self.add(flow, None, Some(messages), false)
+    }
+    /// Finishes the recording, the next flow starts a new one.
+    pub fn finish(&mut self) -> pyo3::PyResult<()> {
+        self.index = 0;
+        self.recording = None;
+        self.degraded_since = None;
         match self.inner.take() {
-            Some(i) => match i.finish() {
-                Ok(p) => fs::remove_dir_all(p).context("failed to remove tmp dir"),
-                Err(e) => {
-                    log::error!("saver failed: {:?}", e);
-                    std::process::abort()
-                }
-            },
+            Some(i) => i
+                .finish()
+                .map_err(|e| SaverError::new_err(format!("{:#}", e))),
             None => Ok(()),
         }
     }
@@ -233,5 +860,21 @@ impl Recorder {
 #[pyo3(name = "http_recorder")]
 pub fn module(_: pyo3::Python, m: &pyo3::types::PyModule) -> pyo3::PyResult<()> {
     pyo3_log::init();
-    m.add_class::<Recorder>()
+    m.add_class::<FlowFilter>()?;
+    m.add_class::<FlowRedactor>()?;
+    m.add_class::<BodyStream>()?;
+    m.add_class::<MessageStream>()?;
+    m.add("RecorderError", m.py().get_type::<RecorderError>())?;
+    m.add("ParseError", m.py().get_type::<ParseError>())?;
+    m.add("SaveError", m.py().get_type::<SaveError>())?;
+    m.add("SaverError", m.py().get_type::<SaverError>())?;
+    m.add_class::<Recorder>()?;
+    m.add_class::<reader::Recording>()?;
+    m.add_class::<reader::Entries>()?;
+    m.add_class::<reader::Entry>()?;
+    m.add_class::<reader::Request>()?;
+    m.add_class::<reader::Response>()?;
+    m.add_class::<reader::Headers>()?;
+    m.add_class::<reader::Cookies>()?;
+    m.add_class::<reader::Content>()
 }
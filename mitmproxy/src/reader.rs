@@ -0,0 +1,453 @@
+use crate::FlowFilter;
+use anyhow::Context;
+use http_recorder::{
+    archive::{
+        reader::{EntryIter, ReadError},
+        RecordingReader,
+    },
+    content::Digest,
+    filter::Filter,
+    request, snippet,
+};
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    pyclass, pymethods,
+    types::PyBytes,
+    Py, PyObject, PyRef, PyResult, Python,
+};
+
+fn timestamp(t: &chrono::DateTime<chrono::Utc>) -> f64 {
+    t.timestamp() as f64 + f64::from(t.timestamp_subsec_nanos()) / 1e9
+}
+
+/// A recording directory, read one pack at a time.
+#[pyclass]
+pub struct Recording {
+    reader: RecordingReader,
+}
+
+#[pymethods]
+impl Recording {
+    #[new]
+    pub fn new(path: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            reader: RecordingReader::open(path).context("failed to open recording")?,
+        })
+    }
+    #[getter]
+    fn path(&self) -> String {
+        self.reader.path().display().to_string()
+    }
+    fn __len__(&self) -> usize {
+        self.reader.info().count as usize
+    }
+    fn __iter__(&self) -> Entries {
+        self.entries(None)
+    }
+    /// Iterates the entries matching `filter`, decoding a pack at a time.
+    /// Failed flows have no response and never match a status or content
+    /// type filter.
+    #[pyo3(signature = (filter = None))]
+    fn entries(&self, filter: Option<&FlowFilter>) -> Entries {
+        Entries {
+            iter: self.reader.entries(),
+            filter: filter.map(|f| f.filter.clone()),
+        }
+    }
+    /// Reads a single entry, seeking to it through the pack index.
+    fn get(&self, py: Python<'_>, index: u32) -> PyResult<Entry> {
+        match self.reader.get(index) {
+            Ok(e) => Entry::new(py, e),
+            Err(ReadError::NotFound(i)) => Err(PyKeyError::new_err(i)),
+            Err(e) => Err(anyhow::Error::new(e)
+                .context(format!("failed to read entry {}", index))
+                .into()),
+        }
+    }
+    fn __repr__(&self) -> String {
+        format!(
+            "<Recording {} ({} entries)>",
+            self.reader.path().display(),
+            self.reader.info().count
+        )
+    }
+}
+
+#[pyclass]
+pub struct Entries {
+    iter: EntryIter,
+    filter: Option<Filter>,
+}
+
+#[pymethods]
+impl Entries {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __next__(&mut self, py: Python<'_>) -> anyhow::Result<Option<Entry>> {
+        for entry in self.iter.by_ref() {
+            let entry = entry.context("failed to read entry")?;
+            if self.filter.as_ref().is_none_or(|f| f.matches(&entry)) {
+                return Ok(Some(Entry::new(py, entry)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[pyclass]
+pub struct Entry {
+    #[pyo3(get)]
+    index: u32,
+    #[pyo3(get)]
+    client_addr: String,
+    #[pyo3(get)]
+    server_addr: Option<String>,
+    /// unix time in seconds, as in mitmproxy
+    #[pyo3(get)]
+    timestamp_start: f64,
+    #[pyo3(get)]
+    timestamp_end: f64,
+    #[pyo3(get)]
+    request: Py<Request>,
+    #[pyo3(get)]
+    response: Option<Py<Response>>,
+    #[pyo3(get)]
+    error: Option<String>,
+}
+impl Entry {
+    fn new(py: Python<'_>, entry: http_recorder::Entry) -> PyResult<Self> {
+        Ok(Self {
+            index: entry.index,
+            client_addr: entry.client_addr.to_string(),
+            server_addr: entry.server_addr.map(|a| a.to_string()),
+            timestamp_start: timestamp(&entry.timings.start_time),
+            timestamp_end: timestamp(&entry.timings.finish_time),
+            request: Py::new(py, Request::new(py, entry.request)?)?,
+            response: match entry.response {
+                Some(r) => Some(Py::new(py, Response::new(py, r)?)?),
+                None => None,
+            },
+            error: entry.error.map(|e| e.message),
+        })
+    }
+}
+
+#[pymethods]
+impl Entry {
+    fn __repr__(&self, py: Python<'_>) -> String {
+        let request = self.request.borrow(py);
+        format!(
+            "<Entry #{} {} {} {}>",
+            self.index,
+            request.method,
+            request.url,
+            match &self.response {
+                Some(r) => r.borrow(py).status_code.to_string(),
+                None => String::from("ERR"),
+            }
+        )
+    }
+}
+
+/// `(name, file_name, content)` of a multipart form field
+type Field = (Option<String>, Option<String>, Py<Content>);
+
+#[pyclass]
+pub struct Request {
+    #[pyo3(get)]
+    http_version: &'static str,
+    #[pyo3(get)]
+    method: String,
+    #[pyo3(get)]
+    url: String,
+    #[pyo3(get)]
+    host: Option<String>,
+    #[pyo3(get)]
+    port: Option<u16>,
+    #[pyo3(get)]
+    path: String,
+    #[pyo3(get)]
+    query: Vec<(String, String)>,
+    #[pyo3(get)]
+    headers: Py<Headers>,
+    #[pyo3(get)]
+    cookies: Py<Cookies>,
+    /// body that is not a form
+    #[pyo3(get)]
+    content: Option<Py<Content>>,
+    /// fields of an urlencoded form body
+    #[pyo3(get)]
+    form: Option<Vec<(String, String)>>,
+    /// fields of a multipart form body
+    #[pyo3(get)]
+    multipart: Option<Vec<Field>>,
+    /// kept for `snippet`
+    request: http_recorder::Request,
+}
+impl Request {
+    fn new(py: Python<'_>, request: http_recorder::Request) -> PyResult<Self> {
+        let original = request.clone();
+        let mut ret = Self {
+            http_version: request.http_version.as_str(),
+            method: request.method.as_str().to_string(),
+            url: request.url.url.to_string(),
+            host: request.url.host.map(|h| h.to_string()),
+            port: request.url.port,
+            path: request.url.path,
+            query: request
+                .url
+                .query
+                .into_iter()
+                .map(|q| (q.name, q.value))
+                .collect(),
+            headers: Py::new(py, Headers::new(request.headers))?,
+            cookies: Py::new(
+                py,
+                Cookies(
+                    request
+                        .cookies
+                        .0
+                        .into_iter()
+                        .map(|c| (c.name, c.value))
+                        .collect(),
+                ),
+            )?,
+            content: None,
+            form: None,
+            multipart: None,
+            request: original,
+        };
+        match request.body {
+            Some(request::Body::Content(c)) => ret.content = Some(Py::new(py, Content::new(c))?),
+            Some(request::Body::UrlEncodedForm(f)) => {
+                ret.form = Some(f.into_iter().map(|f| (f.name, f.value)).collect())
+            }
+            Some(request::Body::MultipartForm(fs)) => {
+                ret.multipart = Some(
+                    fs.into_iter()
+                        .map(|f| Ok((f.name, f.file_name, Py::new(py, Content::new(f.content))?)))
+                        .collect::<PyResult<_>>()?,
+                )
+            }
+            None => (),
+        }
+        Ok(ret)
+    }
+}
+
+#[pymethods]
+impl Request {
+    /// A curl, httpie or reqwest snippet that sends the request again.
+    #[pyo3(signature = (language = "curl"))]
+    fn snippet(&self, language: &str) -> PyResult<String> {
+        let language: snippet::Language = language
+            .parse()
+            .map_err(|e: snippet::InvalidLanguage| PyValueError::new_err(e.to_string()))?;
+        Ok(snippet::generate(&self.request, language))
+    }
+    fn __repr__(&self) -> String {
+        format!("<Request {} {}>", self.method, self.url)
+    }
+}
+
+#[pyclass]
+pub struct Response {
+    #[pyo3(get)]
+    http_version: &'static str,
+    #[pyo3(get)]
+    status_code: u16,
+    #[pyo3(get)]
+    headers: Py<Headers>,
+    #[pyo3(get)]
+    cookies: Py<Cookies>,
+    #[pyo3(get)]
+    content: Option<Py<Content>>,
+}
+impl Response {
+    fn new(py: Python<'_>, response: http_recorder::Response) -> PyResult<Self> {
+        Ok(Self {
+            http_version: response.http_version.as_str(),
+            status_code: response.status_code.0,
+            headers: Py::new(py, Headers::new(response.headers))?,
+            cookies: Py::new(
+                py,
+                Cookies(
+                    response
+                        .cookies
+                        .0
+                        .into_iter()
+                        .map(|c| (c.name, c.value))
+                        .collect(),
+                ),
+            )?,
+            content: match response.content {
+                Some(c) => Some(Py::new(py, Content::new(c))?),
+                None => None,
+            },
+        })
+    }
+}
+
+#[pymethods]
+impl Response {
+    fn __repr__(&self) -> String {
+        format!("<Response {}>", self.status_code)
+    }
+}
+
+/// Header names are matched case-insensitively.
+#[pyclass]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+impl Headers {
+    fn new(headers: http_recorder::Headers) -> Self {
+        Self {
+            fields: headers
+                .0
+                .iter()
+                .map(|h| {
+                    (
+                        h.name.as_str().to_string(),
+                        h.value.to_string_lossy().into_owned(),
+                    )
+                })
+                .collect(),
+        }
+    }
+    fn values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a String> {
+        self.fields
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+}
+
+#[pymethods]
+impl Headers {
+    fn __len__(&self) -> usize {
+        self.fields.len()
+    }
+    fn __contains__(&self, name: &str) -> bool {
+        self.values(name).next().is_some()
+    }
+    /// The first value of `name`.
+    fn __getitem__(&self, name: &str) -> PyResult<String> {
+        self.values(name)
+            .next()
+            .cloned()
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+    #[pyo3(signature = (name, default = None))]
+    fn get(&self, name: &str, default: Option<String>) -> Option<String> {
+        self.values(name).next().cloned().or(default)
+    }
+    fn get_all(&self, name: &str) -> Vec<String> {
+        self.values(name).cloned().collect()
+    }
+    fn items(&self) -> Vec<(String, String)> {
+        self.fields.clone()
+    }
+    fn __repr__(&self) -> String {
+        format!("Headers({:?})", self.fields)
+    }
+}
+
+#[pyclass]
+pub struct Cookies(Vec<(String, String)>);
+
+#[pymethods]
+impl Cookies {
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+    fn __contains__(&self, name: &str) -> bool {
+        self.0.iter().any(|(n, _)| n == name)
+    }
+    fn __getitem__(&self, name: &str) -> PyResult<String> {
+        self.get(name, None)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+    #[pyo3(signature = (name, default = None))]
+    fn get(&self, name: &str, default: Option<String>) -> Option<String> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .or(default)
+    }
+    fn items(&self) -> Vec<(String, String)> {
+        self.0.clone()
+    }
+    fn __repr__(&self) -> String {
+        format!("Cookies({:?})", self.0)
+    }
+}
+
+#[pyclass]
+pub struct Content {
+    #[pyo3(get)]
+    content_type: String,
+    /// of the decoded body
+    #[pyo3(get)]
+    size: u64,
+    #[pyo3(get)]
+    extension: Option<String>,
+    /// hex digest of the decoded body
+    #[pyo3(get)]
+    sha256: String,
+    /// Content-Encoding codings of the transferred body, in the order applied
+    #[pyo3(get)]
+    encoding: Vec<String>,
+    /// false if decoding failed and `data` holds the encoded body
+    #[pyo3(get)]
+    decoded: bool,
+    data: Option<Box<[u8]>>,
+}
+impl Content {
+    fn new(content: http_recorder::content::Content) -> Self {
+        Self {
+            content_type: content.content_type.to_string(),
+            size: content.size,
+            extension: content.extension,
+            sha256: match content.digest {
+                Digest::SHA256(d) => d.to_string(),
+            },
+            encoding: content.encoding.as_ref().map_or(Vec::new(), |e| {
+                e.codings.iter().map(|c| c.as_str().to_string()).collect()
+            }),
+            decoded: content.encoding.as_ref().is_none_or(|e| e.decoded),
+            data: content.data,
+        }
+    }
+}
+
+#[pymethods]
+impl Content {
+    /// `None` if the body was not recorded.
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Option<&'py PyBytes> {
+        self.data.as_ref().map(|d| PyBytes::new(py, d))
+    }
+    /// The body decoded as UTF-8, invalid sequences replaced.
+    fn text(&self) -> Option<String> {
+        self.data
+            .as_ref()
+            .map(|d| String::from_utf8_lossy(d).into_owned())
+    }
+    /// The body parsed with `json.loads`.
+    fn json(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let data = self
+            .data
+            .as_ref()
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("body was not recorded"))?;
+        Ok(py
+            .import("json")?
+            .call_method1("loads", (PyBytes::new(py, data),))?
+            .into())
+    }
+    fn __repr__(&self) -> String {
+        format!("<Content {} ({} bytes)>", self.content_type, self.size)
+    }
+}
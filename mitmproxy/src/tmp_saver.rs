@@ -1,5 +1,8 @@
 use anyhow::Context;
-use http_recorder::{Entries, Entry};
+use http_recorder::{
+    archive::{codec::Encoder, Compression},
+    spill, Entries, Entry,
+};
 use std::{
     fs, io,
     mem::swap,
@@ -10,11 +13,16 @@ use std::{
 
 type TmpEntries = Entries<Vec<Arc<Entry>>>;
 
-fn write_entries(entries: &TmpEntries, path: &Path) -> anyhow::Result<()> {
-    let mut buf = xz2::write::XzEncoder::new(
+fn write_entries(
+    entries: &TmpEntries,
+    path: &Path,
+    compression: Compression,
+) -> anyhow::Result<()> {
+    let mut buf = Encoder::new(
         io::BufWriter::new(fs::File::create(path).context("failed to create pack file")?),
-        9,
-    );
+        compression,
+    )
+    .context("failed to start compression")?;
     ciborium::ser::into_writer(entries, &mut buf).context("failed to write file")?;
     buf.finish()
         .context("failed to finish compression")?
@@ -35,6 +43,7 @@ impl PackerHandle {
 }
 struct Packer {
     path: PathBuf,
+    compression: Compression,
     unpacked_path: PathBuf,
     receiver: mpsc::Receiver<TmpEntries>,
 }
@@ -43,6 +52,7 @@ impl Packer {
         path: PathBuf,
         core: Option<core_affinity::CoreId>,
         unpacked_path: PathBuf,
+        compression: Compression,
     ) -> anyhow::Result<PackerHandle> {
         let (sender, receiver) = mpsc::channel();
         Ok(PackerHandle {
@@ -55,6 +65,7 @@ impl Packer {
                     }
                     Self {
                         path,
+                        compression,
                         unpacked_path,
                         receiver,
                     }
@@ -65,13 +76,14 @@ impl Packer {
     }
     fn run(mut self) -> anyhow::Result<()> {
         for (idx, entries) in self.receiver.into_iter().enumerate() {
-            self.path.push(format!("{}.bin.xz", idx));
+            self.path
+                .push(spill::packed_file_name(idx, self.compression.codec));
             log::info!(
                 "packing requests {}-{}",
                 entries.begin_index,
                 entries.end_index
             );
-            match write_entries(&entries, &self.path) {
+            match write_entries(&entries, &self.path, self.compression) {
                 Ok(()) => {
                     log::info!(
                         "packed requests {}-{} to {}",
@@ -92,7 +104,7 @@ impl Packer {
                 }
             }
             for e in &entries.data {
-                self.unpacked_path.push(format!("{}.bin", e.index));
+                self.unpacked_path.push(spill::unpacked_file_name(e.index));
                 if let Err(e) = fs::remove_file(&self.unpacked_path) {
                     log::error!(
                         "failed to remove file {}: {:?}",
@@ -111,15 +123,17 @@ pub struct TmpSaver {
     tmp_dir: PathBuf,
     unpacked_path: PathBuf,
     entries: TmpEntries,
+    pack_size: u64,
     packer: PackerHandle,
 }
-const TMP_PACK_SIZE: u64 = 256 * (1 << 20); // 256 MiB
+/// Default body bytes of a spill pack, which are held in memory until packed
+pub const TMP_PACK_SIZE: u64 = 256 * (1 << 20); // 256 MiB
 pub enum AddEntryError {
     Io(io::Error),
     Packer,
 }
 fn write_entry(path: &mut PathBuf, entry: &Entry) -> Result<(), io::Error> {
-    path.push(format!("{}.bin", entry.index));
+    path.push(spill::unpacked_file_name(entry.index));
     let mut data = Vec::new();
     ciborium::ser::into_writer(&entry, &mut data).unwrap();
     fs::write(&path, data)?;
@@ -127,24 +141,35 @@ fn write_entry(path: &mut PathBuf, entry: &Entry) -> Result<(), io::Error> {
     Ok(())
 }
 impl TmpSaver {
-    pub fn new(core: Option<core_affinity::CoreId>, entry: &Entry) -> anyhow::Result<Self> {
+    pub fn new(
+        core: Option<core_affinity::CoreId>,
+        entry: &Entry,
+        compression: Compression,
+        pack_size: u64,
+    ) -> anyhow::Result<Self> {
         let tmp_dir = tempfile::Builder::new()
-            .prefix("http-recorder-mitmproxy")
+            .prefix(spill::DIR_PREFIX)
             .tempdir()
             .context("failed to create temp directory")?;
-        let mut unpacked_path = tmp_dir.path().join("unpacked");
+        let mut unpacked_path = tmp_dir.path().join(spill::UNPACKED_DIR);
         fs::create_dir(&unpacked_path).context("failed to create unpacked dir")?;
         write_entry(&mut unpacked_path, entry).context("failed to write entry")?;
         Ok(Self {
             unpacked_path: unpacked_path.clone(),
-            packer: Packer::start(tmp_dir.path().to_path_buf(), core, unpacked_path)
-                .context("failed to start packer")?,
+            packer: Packer::start(
+                tmp_dir.path().to_path_buf(),
+                core,
+                unpacked_path,
+                compression,
+            )
+            .context("failed to start packer")?,
             entries: Entries::new(entry.index, entry.timings.clone()),
-            tmp_dir: tmp_dir.into_path(),
+            pack_size,
+            tmp_dir: tmp_dir.keep(),
         })
     }
     pub fn add_entry(&mut self, entry: Arc<Entry>) -> Result<(), AddEntryError> {
-        if self.entries.content_size() >= TMP_PACK_SIZE {
+        if self.entries.content_size() >= self.pack_size {
             let mut entries = Entries::new(entry.index, entry.timings.clone());
             swap(&mut self.entries, &mut entries);
             self.packer
@@ -153,7 +178,8 @@ impl TmpSaver {
                 .map_err(|_| AddEntryError::Packer)?;
         }
         {
-            self.unpacked_path.push(format!("{}.bin", entry.index));
+            self.unpacked_path
+                .push(spill::unpacked_file_name(entry.index));
             let mut data = Vec::new();
             ciborium::ser::into_writer(entry.as_ref(), &mut data).unwrap();
             fs::write(&self.unpacked_path, data).map_err(AddEntryError::Io)?;
@@ -163,6 +189,10 @@ impl TmpSaver {
         self.entries.data.push(entry);
         Ok(())
     }
+    /// of the spill directory
+    pub fn path(&self) -> &Path {
+        self.tmp_dir.as_path()
+    }
     pub fn finish(self) -> anyhow::Result<PathBuf> {
         self.packer.finish()?;
         Ok(self.tmp_dir)
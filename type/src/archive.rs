@@ -0,0 +1,70 @@
+use crate::Entries;
+use serde::{Deserialize, Serialize};
+
+pub mod blob;
+
+pub mod codec;
+pub use codec::{Codec, Compression};
+
+pub mod reader;
+pub use reader::RecordingReader;
+
+pub mod writer;
+pub use writer::RecordingWriter;
+
+pub const INFO_FILE: &str = "info.json";
+/// Summary of a pack, with the compression it was written with. `None` for
+/// packs written before the compression became configurable, which are xz.
+pub type PackInfo = Entries<Option<Compression>>;
+pub type Info = Entries<Vec<PackInfo>>;
+
+pub fn pack_file_name(pack: u32, codec: Codec) -> String {
+    format!("{}.tar{}", pack, codec.extension())
+}
+pub fn pack_index_file_name(pack: u32) -> String {
+    format!("{}.index.bin", pack)
+}
+pub fn pack_journal_file_name(pack: u32) -> String {
+    format!("{}.journal", pack)
+}
+
+pub const ENTRY_BIN: &str = "entry.bin";
+pub const ENTRY_JSON: &str = "entry.json";
+pub const REQUEST_BODY: &str = "request-body";
+pub const RESPONSE_BODY: &str = "response-body";
+/// CBOR `Vec<WebSocketMessage>` of an entry that upgraded to WebSocket
+pub const WEBSOCKET_MESSAGES: &str = "websocket.bin";
+pub const BLOB_DIR: &str = "blobs";
+
+// Packs are written as a sequence of concatenated compressed streams, so an
+// entry can be read by seeking to the start of its stream and decompressing
+// from there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryOffset {
+    pub index: u32,
+    /// byte offset of the compressed stream in the pack file
+    pub stream: u64,
+    /// offset of the first tar header of the entry inside the decompressed stream
+    pub offset: u64,
+}
+/// A line of the journal of the pack being written, JSON per line. Entries
+/// are journaled once the compressed stream holding them is finished, and the
+/// journal is removed after the pack has been added to the info file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    #[serde(flatten)]
+    pub offset: EntryOffset,
+    /// length of the pack file once the stream of the entry was finished
+    pub end: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackIndex(pub Vec<EntryOffset>);
+impl PackIndex {
+    pub fn find(&self, index: u32) -> Option<&EntryOffset> {
+        self.0
+            .binary_search_by_key(&index, |o| o.index)
+            .ok()
+            .map(|i| &self.0[i])
+    }
+}
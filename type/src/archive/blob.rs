@@ -0,0 +1,114 @@
+use super::{
+    codec::{self, Encoder},
+    Codec, Compression, BLOB_DIR,
+};
+use crate::{
+    content::{Content, Digest, SHA256Digest},
+    request, Entry,
+};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Bodies smaller than this are kept inline in the pack even in dedup mode.
+pub const BLOB_MIN_SIZE: u64 = 4096;
+
+pub fn blob_file_name(digest: &SHA256Digest, codec: Codec) -> String {
+    format!("{}{}", digest, codec.extension())
+}
+
+pub fn blob_path(recording: &Path, digest: &SHA256Digest, codec: Codec) -> PathBuf {
+    recording.join(BLOB_DIR).join(blob_file_name(digest, codec))
+}
+
+fn digest(content: &Content) -> &SHA256Digest {
+    match &content.digest {
+        Digest::SHA256(d) => d,
+    }
+}
+
+// Bodies shared between entries are stored once per digest under `blobs/`,
+// each as its own compressed stream, and left out of the pack.
+pub struct BlobStore {
+    path: PathBuf,
+    compression: Compression,
+    known: HashSet<SHA256Digest>,
+}
+impl BlobStore {
+    pub fn new(recording: &Path, compression: Compression) -> io::Result<Self> {
+        let path = recording.join(BLOB_DIR);
+        fs::create_dir_all(&path)?;
+        Ok(Self {
+            path,
+            compression,
+            known: HashSet::new(),
+        })
+    }
+    pub fn should_store(content: &Content) -> bool {
+        content.data.is_some() && content.size >= BLOB_MIN_SIZE
+    }
+    /// Stores the body of `content` unless a blob with the same digest exists.
+    pub fn insert(&mut self, content: &Content) -> io::Result<()> {
+        let (digest, data) = match &content.data {
+            Some(data) => (digest(content), data),
+            None => return Ok(()),
+        };
+        if self.known.contains(digest) {
+            return Ok(());
+        }
+        let path = self
+            .path
+            .join(blob_file_name(digest, self.compression.codec));
+        if !path.exists() {
+            let tmp_path = path.with_extension("tmp");
+            let mut encoder = Encoder::new(
+                io::BufWriter::new(fs::File::create(&tmp_path)?),
+                self.compression,
+            )?;
+            encoder.write_all(data)?;
+            encoder.finish()?.into_inner()?.sync_all()?;
+            fs::rename(&tmp_path, &path)?;
+        }
+        self.known.insert(digest.clone());
+        Ok(())
+    }
+}
+
+/// Loads the body of `content` from the blob store if it was deduplicated.
+pub fn resolve(recording: &Path, content: &mut Content) -> io::Result<()> {
+    if content.data.is_some() || content.size == 0 {
+        return Ok(());
+    }
+    // the codec of a blob is only known from its file name
+    for c in [Codec::Xz, Codec::Zstd, Codec::None] {
+        let file = match fs::File::open(blob_path(recording, digest(content), c)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let mut data = Vec::with_capacity(content.size as usize);
+        codec::decoder(io::BufReader::new(file), c)?.read_to_end(&mut data)?;
+        content.data = Some(data.into_boxed_slice());
+        break;
+    }
+    Ok(())
+}
+
+pub fn resolve_entry(recording: &Path, entry: &mut Entry) -> io::Result<()> {
+    match &mut entry.request.body {
+        Some(request::Body::Content(c)) => resolve(recording, c)?,
+        Some(request::Body::MultipartForm(fs)) => {
+            for f in fs.iter_mut() {
+                resolve(recording, &mut f.content)?;
+            }
+        }
+        _ => (),
+    }
+    if let Some(c) = entry.response.as_mut().and_then(|r| r.content.as_mut()) {
+        resolve(recording, c)?;
+    }
+    Ok(())
+}
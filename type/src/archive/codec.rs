@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    io::{self, BufRead, Read, Write},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    Xz,
+    Zstd,
+    None,
+}
+impl Codec {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Xz => "xz",
+            Self::Zstd => "zstd",
+            Self::None => "none",
+        }
+    }
+    /// Appended to the names of compressed files, including the dot.
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Xz => ".xz",
+            Self::Zstd => ".zst",
+            Self::None => "",
+        }
+    }
+    pub const fn default_level(&self) -> i32 {
+        match self {
+            Self::Xz => 9,
+            Self::Zstd => 3,
+            Self::None => 0,
+        }
+    }
+    fn levels(&self) -> std::ops::RangeInclusive<i32> {
+        match self {
+            Self::Xz => 0..=9,
+            Self::Zstd => zstd::compression_level_range(),
+            Self::None => 0..=0,
+        }
+    }
+}
+#[derive(Debug, thiserror::Error)]
+#[error("unknown codec {0:?}, expected xz, zstd or none")]
+pub struct InvalidCodec(String);
+impl FromStr for Codec {
+    type Err = InvalidCodec;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xz" => Ok(Self::Xz),
+            "zstd" => Ok(Self::Zstd),
+            "none" => Ok(Self::None),
+            s => Err(InvalidCodec(s.to_string())),
+        }
+    }
+}
+impl Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid {codec} compression level {level}")]
+pub struct InvalidLevel {
+    pub codec: Codec,
+    pub level: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Compression {
+    pub codec: Codec,
+    pub level: i32,
+}
+/// xz at level 9, what every recording was written with before the codec
+/// became configurable.
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            codec: Codec::Xz,
+            level: 9,
+        }
+    }
+}
+impl Compression {
+    /// `level` defaults to the usual level of `codec`.
+    pub fn new(codec: Codec, level: Option<i32>) -> Result<Self, InvalidLevel> {
+        let level = level.unwrap_or(codec.default_level());
+        if !codec.levels().contains(&level) {
+            return Err(InvalidLevel { codec, level });
+        }
+        Ok(Self { codec, level })
+    }
+}
+
+pub enum Encoder<W: Write> {
+    Xz(xz2::write::XzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    None(W),
+}
+impl<W: Write> Encoder<W> {
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        Ok(match compression.codec {
+            Codec::Xz => Self::Xz(xz2::write::XzEncoder::new(writer, compression.level as u32)),
+            Codec::Zstd => Self::Zstd(zstd::stream::write::Encoder::new(
+                writer,
+                compression.level,
+            )?),
+            Codec::None => Self::None(writer),
+        })
+    }
+    pub fn get_ref(&self) -> &W {
+        match self {
+            Self::Xz(e) => e.get_ref(),
+            Self::Zstd(e) => e.get_ref(),
+            Self::None(w) => w,
+        }
+    }
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Xz(e) => e.finish(),
+            Self::Zstd(e) => e.finish(),
+            Self::None(w) => Ok(w),
+        }
+    }
+}
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Xz(e) => e.write(buf),
+            Self::Zstd(e) => e.write(buf),
+            Self::None(w) => w.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Xz(e) => e.flush(),
+            Self::Zstd(e) => e.flush(),
+            Self::None(w) => w.flush(),
+        }
+    }
+}
+
+/// Decompresses all concatenated streams of `reader`.
+pub fn decoder<R: BufRead + Send + 'static>(
+    reader: R,
+    codec: Codec,
+) -> io::Result<Box<dyn Read + Send>> {
+    Ok(match codec {
+        Codec::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        Codec::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+        Codec::None => Box::new(reader),
+    })
+}
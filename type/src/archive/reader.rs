@@ -0,0 +1,525 @@
+use super::{
+    blob, codec, pack_file_name, pack_index_file_name, pack_journal_file_name, Codec, EntryOffset,
+    Info, JournalRecord, PackIndex, ENTRY_BIN, INFO_FILE, REQUEST_BODY, RESPONSE_BODY,
+    WEBSOCKET_MESSAGES,
+};
+use crate::{content::Content, request, websocket::WebSocketMessage, Entry};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufRead, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpenError {
+    #[error("failed to read info file")]
+    Io(
+        #[source]
+        #[from]
+        io::Error,
+    ),
+    #[error("failed to parse info file")]
+    Info(
+        #[source]
+        #[from]
+        serde_json::Error,
+    ),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadError {
+    #[error("failed to open pack {pack}")]
+    Open {
+        pack: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to read pack")]
+    Io(
+        #[source]
+        #[from]
+        io::Error,
+    ),
+    #[error("failed to decode {path}")]
+    Decode {
+        path: String,
+        #[source]
+        source: ciborium::de::Error<io::Error>,
+    },
+    #[error("failed to decode index of pack {pack}")]
+    Index {
+        pack: u32,
+        #[source]
+        source: ciborium::de::Error<io::Error>,
+    },
+    #[error("failed to read blob of entry {index}")]
+    Blob {
+        index: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error("entry {0} not found")]
+    NotFound(u32),
+}
+
+pub type PackStream = Box<dyn Read + Send>;
+
+/// The pack that was being written when the recorder stopped, with the
+/// entries known to be complete in it. Later entries were cut off.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    pub pack: u32,
+    pub codec: Codec,
+    pub records: Vec<JournalRecord>,
+}
+
+pub struct RecordingReader {
+    path: PathBuf,
+    info: Info,
+}
+impl RecordingReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, OpenError> {
+        let path = path.as_ref().to_path_buf();
+        let info = serde_json::from_slice(
+            fs::read(path.join(INFO_FILE))
+                .map_err(OpenError::from)?
+                .as_slice(),
+        )
+        .map_err(OpenError::from)?;
+        Ok(Self { path, info })
+    }
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    pub fn info(&self) -> &Info {
+        &self.info
+    }
+    pub fn pack_count(&self) -> u32 {
+        self.info.data.len() as u32
+    }
+    /// The codec of `pack`, read from the info file.
+    pub fn pack_codec(&self, pack: u32) -> Codec {
+        self.info
+            .data
+            .get(pack as usize)
+            .and_then(|p| p.data)
+            .unwrap_or_default()
+            .codec
+    }
+    pub fn open_pack(&self, pack: u32) -> Result<PackReader<PackStream>, ReadError> {
+        open_pack(self.path.as_path(), pack, self.pack_codec(pack))
+    }
+    pub fn find_pack(&self, index: u32) -> Option<u32> {
+        let packs = &self.info.data;
+        let pack = packs.partition_point(|p| p.begin_index <= index);
+        if pack == 0 || packs[pack - 1].end_index < index {
+            return None;
+        }
+        Some(pack as u32 - 1)
+    }
+    pub fn pack_index(&self, pack: u32) -> Result<Option<PackIndex>, ReadError> {
+        let file = match fs::File::open(self.path.join(pack_index_file_name(pack))) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ReadError::Open { pack, source: e }),
+        };
+        ciborium::de::from_reader(io::BufReader::new(file))
+            .map(Some)
+            .map_err(|e| ReadError::Index { pack, source: e })
+    }
+    // Reads `pack` from the entry at `offset`, up to `end` bytes of the pack
+    // file if set.
+    fn seek_entry(
+        &self,
+        pack: u32,
+        codec: Codec,
+        offset: &EntryOffset,
+        end: Option<u64>,
+    ) -> Result<PackReader<PackStream>, ReadError> {
+        let mut file = fs::File::open(self.path.join(pack_file_name(pack, codec)))
+            .map_err(|e| ReadError::Open { pack, source: e })?;
+        file.seek(io::SeekFrom::Start(offset.stream))
+            .map_err(ReadError::from)?;
+        let file = file.take(end.map_or(u64::MAX, |e| e.saturating_sub(offset.stream)));
+        let mut stream =
+            codec::decoder(io::BufReader::new(file), codec).map_err(ReadError::from)?;
+        if io::copy(&mut (&mut stream).take(offset.offset), &mut io::sink())
+            .map_err(ReadError::from)?
+            != offset.offset
+        {
+            return Err(ReadError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(PackReader::new(stream).with_blobs(self.path.as_path()))
+    }
+    /// Reads an entry of a finished pack, or of the unfinished one listed by
+    /// its journal.
+    pub fn get(&self, index: u32) -> Result<Entry, ReadError> {
+        let mut reader = match self.find_pack(index) {
+            Some(pack) => match self.pack_index(pack)? {
+                Some(pack_index) => {
+                    let offset = pack_index.find(index).ok_or(ReadError::NotFound(index))?;
+                    self.seek_entry(pack, self.pack_codec(pack), offset, None)?
+                }
+                // recordings written before pack indexes existed
+                None => self.open_pack(pack)?,
+            },
+            None => {
+                let journal = self.journal()?.ok_or(ReadError::NotFound(index))?;
+                let record = journal
+                    .records
+                    .iter()
+                    .find(|r| r.offset.index == index)
+                    .ok_or(ReadError::NotFound(index))?;
+                self.seek_entry(
+                    journal.pack,
+                    journal.codec,
+                    &record.offset,
+                    Some(record.end),
+                )?
+            }
+        };
+        while let Some(entry) = reader.next_entry()? {
+            if entry.index == index {
+                return Ok(entry);
+            }
+        }
+        Err(ReadError::NotFound(index))
+    }
+    /// The journal of the pack after the finished ones, `None` if the
+    /// recording was finished.
+    pub fn journal(&self) -> Result<Option<Journal>, ReadError> {
+        let pack = self.pack_count();
+        let file = match fs::File::open(self.path.join(pack_journal_file_name(pack))) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ReadError::Open { pack, source: e }),
+        };
+        let mut records = Vec::new();
+        for line in io::BufReader::new(file).lines() {
+            // only the last line can be invalid, cut off by a crash
+            match serde_json::from_str(line.map_err(ReadError::from)?.as_str()) {
+                Ok(r) => records.push(r),
+                Err(_) => break,
+            }
+        }
+        // the codec of the pack is only known from its file name
+        let codec = [Codec::Xz, Codec::Zstd, Codec::None]
+            .into_iter()
+            .find(|c| self.path.join(pack_file_name(pack, *c)).exists())
+            .ok_or(ReadError::Open {
+                pack,
+                source: io::ErrorKind::NotFound.into(),
+            })?;
+        Ok(Some(Journal {
+            pack,
+            codec,
+            records,
+        }))
+    }
+    /// Reads the entries of an unfinished pack listed by its journal.
+    pub fn journaled_entries(
+        &self,
+        journal: &Journal,
+    ) -> Result<std::iter::Take<PackReader<PackStream>>, ReadError> {
+        let pack = journal.pack;
+        let file = fs::File::open(self.path.join(pack_file_name(pack, journal.codec)))
+            .map_err(|e| ReadError::Open { pack, source: e })?;
+        let end = journal.records.last().map_or(0, |r| r.end);
+        let stream = codec::decoder(io::BufReader::new(file.take(end)), journal.codec)
+            .map_err(ReadError::from)?;
+        Ok(PackReader::new(stream)
+            .with_blobs(self.path.as_path())
+            .take(journal.records.len()))
+    }
+    pub fn entries(&self) -> EntryIter {
+        EntryIter {
+            path: self.path.clone(),
+            codecs: (0..self.pack_count()).map(|p| self.pack_codec(p)).collect(),
+            next_pack: 0,
+            current: None,
+        }
+    }
+}
+
+fn open_pack(path: &Path, pack: u32, codec: Codec) -> Result<PackReader<PackStream>, ReadError> {
+    let file = fs::File::open(path.join(pack_file_name(pack, codec)))
+        .map_err(|e| ReadError::Open { pack, source: e })?;
+    let stream = codec::decoder(io::BufReader::new(file), codec).map_err(ReadError::from)?;
+    Ok(PackReader::new(stream).with_blobs(path))
+}
+
+pub struct EntryIter {
+    path: PathBuf,
+    codecs: Vec<Codec>,
+    next_pack: u32,
+    current: Option<PackReader<PackStream>>,
+}
+impl Iterator for EntryIter {
+    type Item = Result<Entry, ReadError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match &mut self.current {
+                Some(p) => match p.next() {
+                    Some(r) => return Some(r),
+                    None => self.current = None,
+                },
+                None => {
+                    let pack = self.next_pack;
+                    let codec = *self.codecs.get(pack as usize)?;
+                    self.next_pack += 1;
+                    match open_pack(self.path.as_path(), pack, codec) {
+                        Ok(p) => self.current = Some(p),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+enum PackFile {
+    Entry,
+    RequestBody,
+    Field(usize),
+    ResponseBody,
+    WebSocketMessages,
+}
+fn is_entry_dir(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .and_then(|n| n.strip_prefix('#'))
+        .is_some_and(|i| i.parse::<u32>().is_ok())
+}
+fn classify(path: &str) -> Option<(&str, PackFile)> {
+    let (parent, name) = path.rsplit_once('/')?;
+    if is_entry_dir(parent) {
+        let file = if name == ENTRY_BIN {
+            PackFile::Entry
+        } else if name == REQUEST_BODY {
+            PackFile::RequestBody
+        } else if name == RESPONSE_BODY
+            || name
+                .strip_prefix(RESPONSE_BODY)
+                .is_some_and(|e| e.starts_with('.'))
+        {
+            PackFile::ResponseBody
+        } else if name == WEBSOCKET_MESSAGES {
+            PackFile::WebSocketMessages
+        } else {
+            return None;
+        };
+        return Some((parent, file));
+    }
+    let (dir, body) = parent.rsplit_once('/')?;
+    if body == REQUEST_BODY && is_entry_dir(dir) {
+        let idx = name.split('.').next()?.parse().ok()?;
+        Some((dir, PackFile::Field(idx)))
+    } else {
+        None
+    }
+}
+
+struct Bodies {
+    dir: String,
+    request: Option<Box<[u8]>>,
+    fields: BTreeMap<usize, Box<[u8]>>,
+    response: Option<Box<[u8]>>,
+    messages: Vec<WebSocketMessage>,
+}
+fn attach(content: &mut Content, data: Option<Box<[u8]>>) {
+    if content.data.is_none() {
+        content.data = data;
+    }
+}
+impl Bodies {
+    fn new(dir: &str) -> Self {
+        Self {
+            dir: dir.to_string(),
+            request: None,
+            fields: BTreeMap::new(),
+            response: None,
+            messages: Vec::new(),
+        }
+    }
+    fn attach(mut self, entry: &mut Entry) {
+        match &mut entry.request.body {
+            Some(request::Body::Content(c)) => attach(c, self.request),
+            Some(request::Body::MultipartForm(fs)) => {
+                for (idx, f) in fs.iter_mut().enumerate() {
+                    attach(&mut f.content, self.fields.remove(&idx));
+                }
+            }
+            _ => (),
+        }
+        if let Some(c) = entry.response.as_mut().and_then(|r| r.content.as_mut()) {
+            attach(c, self.response);
+        }
+        if let Some(ws) = &mut entry.websocket {
+            if ws.messages.is_empty() {
+                ws.messages = self.messages;
+            }
+        }
+    }
+}
+
+const BLOCK_SIZE: u64 = 512;
+
+// tar::Archive::entries borrows the archive, which would make an owned
+// iterator over a pack impossible, so headers are walked here directly.
+pub struct PackReader<R> {
+    reader: R,
+    bodies: Option<Bodies>,
+    recording: Option<PathBuf>,
+    finished: bool,
+}
+impl<R: Read> PackReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            bodies: None,
+            recording: None,
+            finished: false,
+        }
+    }
+    /// Resolves deduplicated bodies from the blob store of `recording`.
+    pub fn with_blobs(mut self, recording: &Path) -> Self {
+        self.recording = Some(recording.to_path_buf());
+        self
+    }
+    fn read_block(&mut self, block: &mut [u8; BLOCK_SIZE as usize]) -> io::Result<bool> {
+        let mut pos = 0;
+        while pos < block.len() {
+            match self.reader.read(&mut block[pos..]) {
+                Ok(0) if pos == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => pos += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+    fn skip_padding(&mut self, size: u64) -> io::Result<()> {
+        let padding = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+        self.skip(padding)
+    }
+    fn skip(&mut self, size: u64) -> io::Result<()> {
+        if io::copy(&mut (&mut self.reader).take(size), &mut io::sink())? != size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+    fn read_data(&mut self, size: u64) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(size as usize);
+        (&mut self.reader).take(size).read_to_end(&mut data)?;
+        if data.len() as u64 != size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.skip_padding(size)?;
+        Ok(data)
+    }
+    fn next_header(&mut self) -> io::Result<Option<(String, u64, tar::EntryType)>> {
+        let mut block = [0; BLOCK_SIZE as usize];
+        let mut long_name = None;
+        loop {
+            if !self.read_block(&mut block)? || block.iter().all(|b| *b == 0) {
+                return Ok(None);
+            }
+            let header = tar::Header::from_byte_slice(&block);
+            let entry_type = header.entry_type();
+            // directory headers are written without a size field
+            let size = if entry_type.is_dir() {
+                0
+            } else {
+                header.entry_size()?
+            };
+            if entry_type.is_gnu_longname() {
+                let mut name = self.read_data(size)?;
+                while name.last() == Some(&0) {
+                    name.pop();
+                }
+                long_name = Some(name);
+                continue;
+            }
+            let path = match long_name.take() {
+                Some(n) => n,
+                None => header.path_bytes().into_owned(),
+            };
+            return Ok(Some((
+                String::from_utf8_lossy(path.as_slice()).into_owned(),
+                size,
+                entry_type,
+            )));
+        }
+    }
+    pub fn next_entry(&mut self) -> Result<Option<Entry>, ReadError> {
+        loop {
+            let (path, size, entry_type) = match self.next_header().map_err(ReadError::from)? {
+                Some(h) => h,
+                None => return Ok(None),
+            };
+            let (dir, file) = match classify(path.as_str()) {
+                Some(f) if entry_type.is_file() => f,
+                _ => {
+                    self.skip(size)?;
+                    self.skip_padding(size)?;
+                    continue;
+                }
+            };
+            let data = self.read_data(size).map_err(ReadError::from)?;
+            let bodies = match self.bodies.take() {
+                Some(b) if b.dir == dir => self.bodies.insert(b),
+                _ => self.bodies.insert(Bodies::new(dir)),
+            };
+            match file {
+                PackFile::RequestBody => bodies.request = Some(data.into_boxed_slice()),
+                PackFile::Field(idx) => {
+                    bodies.fields.insert(idx, data.into_boxed_slice());
+                }
+                PackFile::ResponseBody => bodies.response = Some(data.into_boxed_slice()),
+                PackFile::WebSocketMessages => {
+                    bodies.messages = ciborium::de::from_reader(data.as_slice())
+                        .map_err(|e| ReadError::Decode { path, source: e })?;
+                }
+                PackFile::Entry => {
+                    let mut entry: Entry = ciborium::de::from_reader(data.as_slice())
+                        .map_err(|e| ReadError::Decode { path, source: e })?;
+                    if let Some(b) = self.bodies.take() {
+                        b.attach(&mut entry);
+                    }
+                    if let Some(r) = &self.recording {
+                        blob::resolve_entry(r.as_path(), &mut entry).map_err(|e| {
+                            ReadError::Blob {
+                                index: entry.index,
+                                source: e,
+                            }
+                        })?;
+                    }
+                    return Ok(Some(entry));
+                }
+            }
+        }
+    }
+}
+impl<R: Read> Iterator for PackReader<R> {
+    type Item = Result<Entry, ReadError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.next_entry() {
+            Ok(Some(e)) => Some(Ok(e)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                if !matches!(e, ReadError::Decode { .. }) {
+                    self.finished = true;
+                }
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,727 @@
+use super::{
+    blob::BlobStore,
+    codec::Encoder,
+    pack_file_name, pack_index_file_name, pack_journal_file_name,
+    reader::{OpenError, ReadError},
+    Codec, Compression, EntryOffset, Info, JournalRecord, PackIndex, PackInfo, RecordingReader,
+    ENTRY_BIN, ENTRY_JSON, INFO_FILE, REQUEST_BODY, RESPONSE_BODY, WEBSOCKET_MESSAGES,
+};
+use crate::{content::Content, request, response::Response, url, Entries, Entry, Request};
+use std::{
+    borrow::Cow,
+    collections::{hash_map, HashMap},
+    fs,
+    io::{self, Write},
+    mem::swap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum WriteError {
+    #[error("failed to create dest dir")]
+    CreateDir(#[source] io::Error),
+    #[error("failed to create pack {pack}")]
+    CreatePack {
+        pack: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to write entry {index}")]
+    Entry {
+        index: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to finish pack {pack}")]
+    FinishPack {
+        pack: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to write info file")]
+    Info(#[source] io::Error),
+    #[error("failed to remove journal of pack {pack}")]
+    RemoveJournal {
+        pack: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to open recording")]
+    Open(
+        #[source]
+        #[from]
+        OpenError,
+    ),
+    #[error("failed to seal unfinished pack {pack}")]
+    Seal {
+        pack: u32,
+        #[source]
+        source: ReadError,
+    },
+}
+
+#[derive(Default)]
+struct DirTree {
+    child: HashMap<String, DirTree>,
+}
+
+fn create_dir<W: io::Write>(
+    tar: &mut tar::Builder<W>,
+    header: &mut tar::Header,
+    path: &Path,
+) -> io::Result<()> {
+    tar.append_data(header, path, io::empty())
+}
+
+// Copy of an entry without the bodies that went to the blob store, so that
+// entry.bin does not carry them either.
+fn without_blobs(entry: &Entry) -> Entry {
+    let strip = |c: &Content| Content {
+        content_type: c.content_type.clone(),
+        digest: c.digest.clone(),
+        extension: c.extension.clone(),
+        size: c.size,
+        encoding: c.encoding.clone(),
+        data: if BlobStore::should_store(c) {
+            None
+        } else {
+            c.data.clone()
+        },
+    };
+    Entry {
+        version: entry.version.clone(),
+        index: entry.index,
+        client_addr: entry.client_addr,
+        server_addr: entry.server_addr,
+        client_tls: entry.client_tls.clone(),
+        server_tls: entry.server_tls.clone(),
+        timings: entry.timings.clone(),
+        request: Request {
+            http_version: entry.request.http_version,
+            method: entry.request.method.clone(),
+            url: entry.request.url.clone(),
+            headers: entry.request.headers.clone(),
+            cookies: entry.request.cookies.clone(),
+            body: entry.request.body.as_ref().map(|b| match b {
+                request::Body::Content(c) => request::Body::Content(strip(c)),
+                request::Body::UrlEncodedForm(f) => request::Body::UrlEncodedForm(f.clone()),
+                request::Body::MultipartForm(f) => request::Body::MultipartForm(
+                    f.iter()
+                        .map(|e| request::MultipartFormEntry {
+                            name: e.name.clone(),
+                            file_name: e.file_name.clone(),
+                            headers: e.headers.clone(),
+                            content: strip(&e.content),
+                        })
+                        .collect(),
+                ),
+            }),
+            form_encoding: entry.request.form_encoding.clone(),
+        },
+        response: entry.response.as_ref().map(|r| Response {
+            http_version: r.http_version,
+            status_code: r.status_code,
+            headers: r.headers.clone(),
+            cookies: r.cookies.clone(),
+            content: r.content.as_ref().map(strip),
+            events: r.events.clone(),
+            segment: r.segment,
+        }),
+        error: entry.error.clone(),
+        websocket: entry.websocket.clone(),
+        redactions: entry.redactions.clone(),
+    }
+}
+
+const STREAM_SIZE: u64 = 16 * (1 << 20); // 16 MiB
+
+// Counts the compressed bytes, which locate the streams in the pack file.
+struct Counter {
+    file: io::BufWriter<fs::File>,
+    written: u64,
+}
+impl io::Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn stream_closed() -> io::Error {
+    io::Error::other("pack stream closed")
+}
+struct PackStream {
+    encoder: Option<Encoder<Counter>>,
+    compression: Compression,
+    stream: u64,
+    stream_size: u64,
+}
+impl PackStream {
+    fn new(file: fs::File, compression: Compression) -> io::Result<Self> {
+        Ok(Self {
+            encoder: Some(Encoder::new(
+                Counter {
+                    file: io::BufWriter::new(file),
+                    written: 0,
+                },
+                compression,
+            )?),
+            compression,
+            stream: 0,
+            stream_size: 0,
+        })
+    }
+    fn encoder(&mut self) -> io::Result<&mut Encoder<Counter>> {
+        self.encoder.as_mut().ok_or_else(stream_closed)
+    }
+    fn stream_size(&self) -> u64 {
+        self.stream_size
+    }
+    /// Finishes the current stream and writes it to the pack file.
+    fn next_stream(&mut self) -> io::Result<()> {
+        let mut counter = self.encoder.take().ok_or_else(stream_closed)?.finish()?;
+        counter.file.flush()?;
+        self.stream = counter.written;
+        self.stream_size = 0;
+        self.encoder = Some(Encoder::new(counter, self.compression)?);
+        Ok(())
+    }
+    fn finish(mut self) -> io::Result<io::BufWriter<fs::File>> {
+        Ok(self
+            .encoder
+            .take()
+            .ok_or_else(stream_closed)?
+            .finish()?
+            .file)
+    }
+}
+impl io::Write for PackStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.encoder()?.write(buf)?;
+        self.stream_size += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder()?.flush()
+    }
+}
+
+struct TarFile {
+    entry_info: PackInfo,
+    tar_file: tar::Builder<PackStream>,
+    dir_tree: DirTree,
+    index: PackIndex,
+    index_path: PathBuf,
+    journal: fs::File,
+    journaled: usize,
+}
+impl TarFile {
+    fn new(
+        dest: &Path,
+        tar_index: u32,
+        entry: &Entry,
+        compression: Compression,
+    ) -> io::Result<Self> {
+        let tar_path = dest.join(pack_file_name(tar_index, compression.codec));
+        let mut entry_info = Entries::new(entry.index, entry.timings.clone());
+        entry_info.data = Some(compression);
+        let tar_file = tar::Builder::new(PackStream::new(
+            fs::File::options()
+                .write(true)
+                .create_new(true)
+                .open(tar_path)?,
+            compression,
+        )?);
+        let journal = fs::File::options()
+            .append(true)
+            .create_new(true)
+            .open(dest.join(pack_journal_file_name(tar_index)))?;
+        Ok(Self {
+            entry_info,
+            tar_file,
+            dir_tree: DirTree::default(),
+            index: PackIndex::default(),
+            index_path: dest.join(pack_index_file_name(tar_index)),
+            journal,
+            journaled: 0,
+        })
+    }
+    // Journals the entries of the streams finished since the last call.
+    fn write_journal(&mut self) -> io::Result<()> {
+        let end = self.tar_file.get_ref().stream;
+        let mut lines = Vec::new();
+        for offset in &self.index.0[self.journaled..] {
+            serde_json::to_writer(
+                &mut lines,
+                &JournalRecord {
+                    offset: *offset,
+                    end,
+                },
+            )
+            .unwrap();
+            lines.push(b'\n');
+        }
+        // a single write, so that a crash can only cut off the last line
+        self.journal.write_all(lines.as_slice())?;
+        self.journaled = self.index.0.len();
+        Ok(())
+    }
+    fn add_entry_parent(&mut self, entry: &Entry) -> io::Result<PathBuf> {
+        let mut header = {
+            let mut ret = tar::Header::new_gnu();
+            ret.set_mode(0o755);
+            ret.set_entry_type(tar::EntryType::Directory);
+            ret
+        };
+        let mut path = PathBuf::new();
+        let mut node = {
+            let name = match &entry.request.url.host {
+                Some(h) => match h {
+                    url::Host::Domain(d) => d.to_string(),
+                    url::Host::Addr(a) => a.to_string(),
+                },
+                None => String::from("unknown"),
+            };
+            match self.dir_tree.child.entry(name) {
+                hash_map::Entry::Occupied(o) => {
+                    path.push(o.key());
+                    o.into_mut()
+                }
+                hash_map::Entry::Vacant(v) => {
+                    path.push(v.key());
+                    create_dir(&mut self.tar_file, &mut header, path.as_path())?;
+                    v.insert(DirTree::default())
+                }
+            }
+        };
+        if let Some(ps) = entry.request.url.url.path_segments() {
+            for p in ps {
+                if p.is_empty() {
+                    break;
+                }
+                node = match node.child.entry(p.to_string()) {
+                    hash_map::Entry::Occupied(o) => {
+                        path.push(o.key());
+                        o.into_mut()
+                    }
+                    hash_map::Entry::Vacant(v) => {
+                        path.push(v.key());
+                        create_dir(&mut self.tar_file, &mut header, path.as_path())?;
+                        v.insert(DirTree::default())
+                    }
+                };
+            }
+        }
+        path.push(format!("#{}", entry.index));
+        create_dir(&mut self.tar_file, &mut header, path.as_path())?;
+        Ok(path)
+    }
+    fn add_entry(&mut self, entry: &Entry, mut blobs: Option<&mut BlobStore>) -> io::Result<()> {
+        let mut file_header = {
+            let mut ret = tar::Header::new_gnu();
+            ret.set_mode(0o444);
+            ret
+        };
+        {
+            let stream = self.tar_file.get_mut();
+            if stream.stream_size() >= STREAM_SIZE {
+                stream.next_stream()?;
+                self.write_journal()?;
+            }
+            let stream = self.tar_file.get_ref();
+            self.index.0.push(EntryOffset {
+                index: entry.index,
+                stream: stream.stream,
+                offset: stream.stream_size(),
+            });
+        }
+        let mut path = self.add_entry_parent(entry)?;
+        if let Some(body) = &entry.request.body {
+            match body {
+                request::Body::Content(c) if blobs.is_some() && BlobStore::should_store(c) => {
+                    blobs.as_mut().unwrap().insert(c)?;
+                }
+                request::Body::Content(Content {
+                    data: Some(data), ..
+                }) => {
+                    path.push(REQUEST_BODY);
+                    file_header.set_size(data.len() as u64);
+                    self.tar_file
+                        .append_data(&mut file_header, &path, data.as_ref())?;
+                    path.pop();
+                }
+                request::Body::MultipartForm(v) if !v.is_empty() => {
+                    path.push(REQUEST_BODY);
+                    let mut dir_header = tar::Header::new_gnu();
+                    dir_header.set_mode(0o755);
+                    dir_header.set_entry_type(tar::EntryType::Directory);
+                    self.tar_file
+                        .append_data(&mut dir_header, &path, io::empty())?;
+                    for (idx, f) in v.iter().enumerate() {
+                        if let Some(b) = blobs
+                            .as_mut()
+                            .filter(|_| BlobStore::should_store(&f.content))
+                        {
+                            b.insert(&f.content)?;
+                        } else if let Some(data) = &f.content.data {
+                            match &f.content.extension {
+                                Some(ext) => path.push(format!("{}.{}", idx, ext)),
+                                None => path.push(idx.to_string()),
+                            }
+                            file_header.set_size(data.len() as u64);
+                            self.tar_file
+                                .append_data(&mut file_header, &path, data.as_ref())?;
+                            path.pop();
+                        }
+                    }
+                    path.pop();
+                }
+                _ => (),
+            }
+        }
+        if let Some(content) = entry.response.as_ref().and_then(|r| r.content.as_ref()) {
+            if let Some(b) = blobs.as_mut().filter(|_| BlobStore::should_store(content)) {
+                b.insert(content)?;
+            } else if let Some(data) = &content.data {
+                match &content.extension {
+                    Some(ext) => path.push(format!("{}.{}", RESPONSE_BODY, ext)),
+                    None => path.push(RESPONSE_BODY),
+                }
+                file_header.set_size(data.len() as u64);
+                self.tar_file
+                    .append_data(&mut file_header, &path, data.as_ref())?;
+                path.pop();
+            }
+        }
+        let mut packed = match blobs {
+            Some(_) => Cow::Owned(without_blobs(entry)),
+            None => Cow::Borrowed(entry),
+        };
+        if let Some(ws) = entry.websocket.as_ref().filter(|w| !w.messages.is_empty()) {
+            path.push(WEBSOCKET_MESSAGES);
+            let data = {
+                let mut r = Vec::new();
+                ciborium::ser::into_writer(&ws.messages, &mut r).unwrap();
+                r
+            };
+            file_header.set_size(data.len() as u64);
+            self.tar_file
+                .append_data(&mut file_header, &path, data.as_slice())?;
+            path.pop();
+            if let Some(ws) = &mut packed.to_mut().websocket {
+                ws.messages.clear();
+            }
+        }
+        {
+            path.push(ENTRY_BIN);
+            let data = {
+                let mut r = Vec::new();
+                ciborium::ser::into_writer(packed.as_ref(), &mut r).unwrap();
+                r
+            };
+            file_header.set_size(data.len() as u64);
+            self.tar_file
+                .append_data(&mut file_header, &path, data.as_slice())?;
+            path.pop();
+        }
+        {
+            path.push(ENTRY_JSON);
+            let data = serde_json::to_vec(packed.as_ref()).unwrap();
+            file_header.set_size(data.len() as u64);
+            self.tar_file
+                .append_data(&mut file_header, &path, data.as_slice())?;
+            path.pop();
+        }
+        self.entry_info.update(entry);
+        Ok(())
+    }
+    /// Finishes the pack and its index, leaving the journal to be removed
+    /// once the info file lists the pack.
+    fn finish(self) -> io::Result<PackInfo> {
+        self.tar_file
+            .into_inner()?
+            .finish()?
+            .into_inner()?
+            .sync_all()?;
+        let index = {
+            let mut r = Vec::new();
+            ciborium::ser::into_writer(&self.index, &mut r).unwrap();
+            r
+        };
+        fs::write(self.index_path, index)?;
+        Ok(self.entry_info)
+    }
+}
+
+pub const MAX_PACK: u64 = 512 * (1 << 20); // 512 MiB
+
+/// When to close a pack and start the next one. A pack is closed before an
+/// entry is added once any of the limits is reached, so a pack can overshoot
+/// `max_size` by one entry and is only closed when the next entry arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    /// body bytes of a pack
+    pub max_size: Option<u64>,
+    pub max_entries: Option<u32>,
+    /// length of the intervals of entry start times covered by a pack,
+    /// aligned to the unix epoch so that an hour starts packs on the hour
+    pub interval: Option<Duration>,
+}
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_size: Some(MAX_PACK),
+            max_entries: None,
+            interval: None,
+        }
+    }
+}
+impl Rotation {
+    fn slot(interval: Duration, time: chrono::DateTime<chrono::Utc>) -> i64 {
+        let interval = (interval.as_millis() as i64).max(1);
+        time.timestamp_millis().div_euclid(interval)
+    }
+    pub fn should_rotate<T: Default>(&self, pack: &Entries<T>, entry: &Entry) -> bool {
+        if pack.count == 0 {
+            return false;
+        }
+        self.max_size.is_some_and(|s| pack.content_size() >= s)
+            || self.max_entries.is_some_and(|n| pack.count >= n)
+            || self.interval.is_some_and(|i| {
+                Self::slot(i, pack.begin_time.start_time) != Self::slot(i, entry.timings.start_time)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// store bodies of at least `blob::BLOB_MIN_SIZE` bytes once per digest
+    /// under `blobs/` instead of inside the packs
+    pub dedup: bool,
+    /// of packs and blobs
+    pub compression: Compression,
+    pub rotation: Rotation,
+}
+
+// Replaces the info file atomically, so that it always lists finished packs
+// only, even if the process dies while writing it.
+fn write_info(recording: &Path, info: &Info) -> Result<(), WriteError> {
+    let path = recording.join(INFO_FILE);
+    let tmp_path = path.with_extension("json.tmp");
+    let write = || -> io::Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(serde_json::to_vec(info).unwrap().as_slice())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)
+    };
+    write().map_err(WriteError::Info)
+}
+fn remove_journal(recording: &Path, pack: u32) -> Result<(), WriteError> {
+    fs::remove_file(recording.join(pack_journal_file_name(pack)))
+        .map_err(|e| WriteError::RemoveJournal { pack, source: e })
+}
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// Cuts the unfinished pack of `reader` after the last entry its journal lists
+// and adds it to `info` as a finished pack. Returns false, after removing the
+// pack, if no entry of it was complete.
+fn seal_pack(
+    reader: &RecordingReader,
+    info: &mut Info,
+    compression: Compression,
+) -> Result<bool, ReadError> {
+    let pack = reader.pack_count();
+    let journal = match reader.journal()? {
+        Some(j) if !j.records.is_empty() => j,
+        _ => {
+            for codec in [Codec::Xz, Codec::Zstd, Codec::None] {
+                remove_if_exists(&reader.path().join(pack_file_name(pack, codec)))?;
+            }
+            remove_if_exists(&reader.path().join(pack_journal_file_name(pack)))?;
+            return Ok(false);
+        }
+    };
+    let end = journal.records.last().map_or(0, |r| r.end);
+    let file = fs::File::options()
+        .write(true)
+        .open(reader.path().join(pack_file_name(pack, journal.codec)))?;
+    file.set_len(end)?;
+    file.sync_all()?;
+    let mut pack_info: Option<PackInfo> = None;
+    for entry in reader.journaled_entries(&journal)? {
+        let entry = entry?;
+        pack_info
+            .get_or_insert_with(|| Entries::new(entry.index, entry.timings.clone()))
+            .update(&entry);
+        info.update(&entry);
+    }
+    let mut pack_info = pack_info.ok_or(ReadError::NotFound(journal.records[0].offset.index))?;
+    // the level is not journaled, only the codec from the file name is known
+    pack_info.data = Some(if journal.codec == compression.codec {
+        compression
+    } else {
+        Compression::new(journal.codec, None).unwrap()
+    });
+    info.data.push(pack_info);
+    let index = {
+        let mut r = Vec::new();
+        let index = PackIndex(journal.records.iter().map(|r| r.offset).collect());
+        ciborium::ser::into_writer(&index, &mut r).unwrap();
+        r
+    };
+    fs::write(reader.path().join(pack_index_file_name(pack)), index)?;
+    Ok(true)
+}
+
+pub struct RecordingWriter {
+    count: u32,
+    path: PathBuf,
+    compression: Compression,
+    rotation: Rotation,
+    entries: Info,
+    blobs: Option<BlobStore>,
+    tar_file: TarFile,
+}
+impl RecordingWriter {
+    pub fn new<P: AsRef<Path>>(path: P, entry: &Entry) -> Result<Self, WriteError> {
+        Self::with_options(path, entry, WriteOptions::default())
+    }
+    pub fn with_options<P: AsRef<Path>>(
+        path: P,
+        entry: &Entry,
+        options: WriteOptions,
+    ) -> Result<Self, WriteError> {
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(&path).map_err(WriteError::CreateDir)?;
+        let entries = Entries::new(entry.index, entry.timings.clone());
+        // a recording without finished packs is still a valid one
+        write_info(path.as_path(), &entries)?;
+        Self::start(path, entries, 0, entry, options)
+    }
+    /// Continues a recording whose writer stopped without finishing it, e.g.
+    /// after a write error. The unfinished pack is sealed after the last
+    /// entry its journal lists, later entries of it are lost, and `entry`
+    /// starts a new pack.
+    pub fn resume<P: AsRef<Path>>(
+        path: P,
+        entry: &Entry,
+        options: WriteOptions,
+    ) -> Result<Self, WriteError> {
+        let path = path.as_ref().to_path_buf();
+        let reader = RecordingReader::open(&path).map_err(WriteError::from)?;
+        let mut entries = reader.info().clone();
+        let mut pack = reader.pack_count();
+        if seal_pack(&reader, &mut entries, options.compression)
+            .map_err(|e| WriteError::Seal { pack, source: e })?
+        {
+            write_info(path.as_path(), &entries)?;
+            remove_journal(path.as_path(), pack)?;
+            pack += 1;
+        }
+        Self::start(path, entries, pack, entry, options)
+    }
+    fn start(
+        path: PathBuf,
+        entries: Info,
+        pack: u32,
+        entry: &Entry,
+        options: WriteOptions,
+    ) -> Result<Self, WriteError> {
+        Ok(Self {
+            count: pack,
+            blobs: if options.dedup {
+                Some(
+                    BlobStore::new(path.as_path(), options.compression)
+                        .map_err(WriteError::CreateDir)?,
+                )
+            } else {
+                None
+            },
+            entries,
+            tar_file: TarFile::new(path.as_path(), pack, entry, options.compression)
+                .map_err(|e| WriteError::CreatePack { pack, source: e })?,
+            compression: options.compression,
+            rotation: options.rotation,
+            path,
+        })
+    }
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    pub fn add_entry(&mut self, entry: &Entry) -> Result<(), WriteError> {
+        if self
+            .rotation
+            .should_rotate(&self.tar_file.entry_info, entry)
+        {
+            let pack = self.count + 1;
+            let mut tar_file = TarFile::new(self.path.as_path(), pack, entry, self.compression)
+                .map_err(|e| WriteError::CreatePack { pack, source: e })?;
+            swap(&mut self.tar_file, &mut tar_file);
+            self.entries
+                .data
+                .push(tar_file.finish().map_err(|e| WriteError::FinishPack {
+                    pack: self.count,
+                    source: e,
+                })?);
+            // readers can open the closed packs while this one is written
+            write_info(&self.path, &self.entries)?;
+            remove_journal(&self.path, self.count)?;
+            self.count = pack;
+        }
+        self.tar_file
+            .add_entry(entry, self.blobs.as_mut())
+            .map_err(|e| WriteError::Entry {
+                index: entry.index,
+                source: e,
+            })?;
+        self.entries.update(entry);
+        Ok(())
+    }
+    pub fn finish(mut self) -> Result<Info, WriteError> {
+        self.entries
+            .data
+            .push(self.tar_file.finish().map_err(|e| WriteError::FinishPack {
+                pack: self.count,
+                source: e,
+            })?);
+        write_info(&self.path, &self.entries)?;
+        remove_journal(&self.path, self.count)?;
+        Ok(self.entries)
+    }
+}
+
+/// Writes the entries passed to `write` by `produce` as a new recording at
+/// `path`, which is created with the first entry. When `produce` fails, the
+/// entries written before are still finished into a readable recording and
+/// the error is returned. Returns None when no entry was written.
+pub fn write_recording<E, F>(
+    path: &Path,
+    options: WriteOptions,
+    produce: F,
+) -> Result<Option<Info>, E>
+where
+    E: From<WriteError>,
+    F: FnOnce(&mut dyn FnMut(&Entry) -> Result<(), WriteError>) -> Result<(), E>,
+{
+    let mut writer: Option<RecordingWriter> = None;
+    let ret = produce(&mut |entry| {
+        let w = match &mut writer {
+            Some(w) => w,
+            None => writer.insert(RecordingWriter::with_options(path, entry, options.clone())?),
+        };
+        w.add_entry(entry)
+    });
+    let info = writer.map(RecordingWriter::finish).transpose();
+    ret?;
+    info.map_err(E::from)
+}
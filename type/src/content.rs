@@ -1,4 +1,9 @@
+use crate::header::{self, Headers};
 use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+};
 
 mod serde_mime {
     use mime::Mime;
@@ -83,14 +88,214 @@ mod serde_digest {
 }
 
 pub const SHA256_OUTPUT_SIZE: usize = 32;
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct SHA256Digest(#[serde(with = "serde_digest")] pub [u8; SHA256_OUTPUT_SIZE]);
+impl std::fmt::Display for SHA256Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(hex::encode(self.0).as_str())
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "algo", content = "hash")]
 pub enum Digest {
     SHA256(SHA256Digest),
 }
+impl Digest {
+    pub fn sha256(data: &[u8]) -> Self {
+        use sha2::{digest::FixedOutput, Digest, Sha256};
+        Self::SHA256(SHA256Digest(
+            Sha256::new_with_prefix(data).finalize_fixed().into(),
+        ))
+    }
+}
+
+/// Payloads are only decoded up to this many times their transferred size,
+/// and up to [`MAX_DECODED_SIZE`]. Larger ones, e.g. decompression bombs, are
+/// kept encoded instead of inflated in memory.
+pub const MAX_DECODE_RATIO: u64 = 1024;
+pub const MAX_DECODED_SIZE: u64 = 256 * (1 << 20); // 256 MiB
+
+// Reads `reader` to the end, failing once it yields more than `limit` bytes.
+fn read_limited<R: Read>(reader: R, limit: u64, buf: &mut Vec<u8>) -> io::Result<()> {
+    reader.take(limit.saturating_add(1)).read_to_end(buf)?;
+    match buf.len() as u64 > limit {
+        true => Err(io::Error::other("decoded body too large")),
+        false => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentCoding {
+    Gzip,
+    Deflate,
+    Br,
+    Zstd,
+    Other(Box<str>),
+}
+impl ContentCoding {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Br => "br",
+            Self::Zstd => "zstd",
+            Self::Other(o) => o,
+        }
+    }
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => return None,
+            "gzip" | "x-gzip" => Self::Gzip,
+            "deflate" => Self::Deflate,
+            "br" => Self::Br,
+            "zstd" => Self::Zstd,
+            v => Self::Other(v.into()),
+        })
+    }
+    // codings listed in all Content-Encoding headers, in the order applied
+    pub fn parse_headers(headers: &Headers) -> Vec<Self> {
+        headers
+            .0
+            .iter()
+            .filter(|h| h.name == header::CONTENT_ENCODING)
+            .flat_map(|h| {
+                h.value
+                    .to_string_lossy()
+                    .split(',')
+                    .filter_map(Self::parse)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+    fn decode(&self, data: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+        let mut ret = Vec::new();
+        match self {
+            Self::Gzip => {
+                read_limited(flate2::read::MultiGzDecoder::new(data), limit, &mut ret)?;
+            }
+            // some servers send raw deflate data instead of the zlib format
+            Self::Deflate => {
+                if read_limited(flate2::read::ZlibDecoder::new(data), limit, &mut ret).is_err() {
+                    ret.clear();
+                    read_limited(flate2::read::DeflateDecoder::new(data), limit, &mut ret)?;
+                }
+            }
+            Self::Br => {
+                read_limited(brotli::Decompressor::new(data, 4096), limit, &mut ret)?;
+            }
+            Self::Zstd => {
+                read_limited(zstd::stream::read::Decoder::new(data)?, limit, &mut ret)?;
+            }
+            Self::Other(_) => return Err(io::ErrorKind::Unsupported.into()),
+        }
+        Ok(ret)
+    }
+    fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut e =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                e.write_all(data)?;
+                e.finish()
+            }
+            Self::Deflate => {
+                let mut e =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                e.write_all(data)?;
+                e.finish()
+            }
+            Self::Br => {
+                let mut e = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+                e.write_all(data)?;
+                Ok(e.into_inner())
+            }
+            Self::Zstd => zstd::stream::encode_all(data, 0),
+            Self::Other(_) => Err(io::ErrorKind::Unsupported.into()),
+        }
+    }
+    /// Applies `codings` in order to decoded data, the inverse of
+    /// [`Payload::decode`]. The result matches the transferred bytes in
+    /// meaning but not necessarily byte for byte.
+    pub fn encode_all(codings: &[Self], data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut data = Cow::Borrowed(data);
+        for c in codings {
+            data = Cow::Owned(c.encode(&data)?);
+        }
+        Ok(data.into_owned())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Encoding {
+    pub codings: Vec<ContentCoding>,
+    // size as transferred, unknown when the payload was recorded decoded
+    pub encoded_size: Option<u64>,
+    // false when decoding failed, or the decoded payload would have been over
+    // the limits, and data still holds the encoded payload
+    pub decoded: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Payload<'a> {
+    // bytes as transferred, with Content-Encoding still applied
+    Raw(&'a [u8]),
+    // bytes with Content-Encoding already removed
+    Decoded(&'a [u8]),
+}
+impl<'a> Payload<'a> {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Raw(d) | Self::Decoded(d) => d.is_empty(),
+        }
+    }
+    pub fn decode(self, headers: &Headers) -> (Option<Encoding>, Cow<'a, [u8]>) {
+        let codings = ContentCoding::parse_headers(headers);
+        if codings.is_empty() {
+            return (
+                None,
+                Cow::Borrowed(match self {
+                    Self::Raw(d) | Self::Decoded(d) => d,
+                }),
+            );
+        }
+        match self {
+            Self::Decoded(d) => (
+                Some(Encoding {
+                    codings,
+                    encoded_size: None,
+                    decoded: true,
+                }),
+                Cow::Borrowed(d),
+            ),
+            Self::Raw(raw) => {
+                let limit = (raw.len() as u64)
+                    .saturating_mul(MAX_DECODE_RATIO)
+                    .min(MAX_DECODED_SIZE);
+                let mut data = Cow::Borrowed(raw);
+                let mut decoded = true;
+                for c in codings.iter().rev() {
+                    match c.decode(&data, limit) {
+                        Ok(d) => data = Cow::Owned(d),
+                        Err(_) => {
+                            data = Cow::Borrowed(raw);
+                            decoded = false;
+                            break;
+                        }
+                    }
+                }
+                (
+                    Some(Encoding {
+                        codings,
+                        encoded_size: Some(raw.len() as u64),
+                        decoded,
+                    }),
+                    data,
+                )
+            }
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Content {
@@ -99,20 +304,23 @@ pub struct Content {
     pub digest: Digest,
     pub extension: Option<String>,
     pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Encoding>,
     #[serde(with = "serde_data")]
     pub data: Option<Box<[u8]>>,
 }
 impl Content {
     pub fn from_mime<CT: AsRef<str>>(url: &str, content_type: Option<CT>, data: Box<[u8]>) -> Self {
         use mime_sniffer::MimeTypeSnifferExt;
-        use sha2::{digest::FixedOutput, Digest, Sha256};
         let content_type = match content_type {
             Some(ct) => mime_sniffer::HttpRequest {
                 url: &url,
                 content: &data,
                 type_hint: ct.as_ref(),
             }
-            .sniff_mime_type_ext(),
+            .sniff_mime_type_ext()
+            // the sniffer only answers for ambiguous hints, trust the rest
+            .or_else(|| ct.as_ref().parse().ok()),
             None => mime_sniffer::HttpRequest {
                 url: &url,
                 content: &data,
@@ -122,17 +330,49 @@ impl Content {
         }
         .unwrap_or(mime::APPLICATION_OCTET_STREAM);
         Self {
-            digest: {
-                self::Digest::SHA256(SHA256Digest(
-                    Sha256::new_with_prefix(data.as_ref())
-                        .finalize_fixed()
-                        .into(),
-                ))
-            },
+            digest: self::Digest::sha256(data.as_ref()),
             extension: mime2ext::mime2ext(&content_type).map(|v| v.to_string()),
             content_type,
             size: data.len() as u64,
+            encoding: None,
             data: Some(data),
         }
     }
+    /// Replaces the body, keeping the content type and the original encoding.
+    pub fn set_data(&mut self, data: Box<[u8]>) {
+        self.digest = Digest::sha256(data.as_ref());
+        self.size = data.len() as u64;
+        self.data = Some(data);
+    }
+    pub fn from_payload<CT: AsRef<str>>(
+        url: &str,
+        content_type: Option<CT>,
+        headers: &Headers,
+        payload: Payload<'_>,
+    ) -> Self {
+        let (encoding, data) = payload.decode(headers);
+        Self {
+            encoding,
+            ..Self::from_mime(url, content_type, data.into_owned().into_boxed_slice())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_limit() {
+        let headers = Headers::parse([("Content-Encoding", "zstd")].into_iter()).unwrap();
+        let raw = zstd::stream::encode_all(&[0; 1 << 20][..], 19).unwrap();
+        let (encoding, data) = Payload::Raw(&raw).decode(&headers);
+        assert!(!encoding.unwrap().decoded);
+        assert_eq!(data, raw);
+
+        let raw = zstd::stream::encode_all(&b"0123456789"[..], 0).unwrap();
+        let (encoding, data) = Payload::Raw(&raw).decode(&headers);
+        assert!(encoding.unwrap().decoded);
+        assert_eq!(data, &b"0123456789"[..]);
+    }
 }
@@ -0,0 +1,173 @@
+use crate::{request, Entry, Request, Response};
+use serde::{Deserialize, Serialize};
+
+/// Which entries get recorded. Empty include lists match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// host globs, e.g. `*.example.com`
+    pub hosts: Vec<String>,
+    pub ignore_hosts: Vec<String>,
+    pub path_prefixes: Vec<String>,
+    pub path_regexes: Vec<String>,
+    pub methods: Vec<String>,
+    /// status codes or inclusive ranges, e.g. `200-299` or `404`, never
+    /// matched by failed flows
+    pub status: Vec<String>,
+    /// content type globs of the response, e.g. `image/*`, never matched by
+    /// failed flows
+    pub content_types: Vec<String>,
+    pub ignore_content_types: Vec<String>,
+    /// entries with a larger request or response body are dropped
+    pub max_body_size: Option<u64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidFilter {
+    #[error("invalid pattern {0:?}")]
+    Pattern(String, #[source] regex::Error),
+    #[error("invalid status range {0:?}")]
+    Status(String),
+}
+
+// `*` matches any run of characters and `?` a single one.
+fn glob(pattern: &str) -> Result<regex::Regex, InvalidFilter> {
+    let mut re = String::from("(?i)^");
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(regex::escape(c.encode_utf8(&mut [0; 4])).as_str()),
+        }
+    }
+    re.push('$');
+    regex::Regex::new(re.as_str()).map_err(|e| InvalidFilter::Pattern(pattern.to_string(), e))
+}
+fn globs(patterns: &[String]) -> Result<Vec<regex::Regex>, InvalidFilter> {
+    patterns.iter().map(|p| glob(p.as_str())).collect()
+}
+
+fn status_range(s: &str) -> Result<(u16, u16), InvalidFilter> {
+    let invalid = || InvalidFilter::Status(s.to_string());
+    let (start, end) = s.split_once('-').unwrap_or((s, s));
+    let start = start.trim().parse().map_err(|_| invalid())?;
+    let end = end.trim().parse().map_err(|_| invalid())?;
+    if start > end {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+fn body_size(request: &Request) -> u64 {
+    match &request.body {
+        Some(request::Body::Content(c)) => c.size,
+        Some(request::Body::MultipartForm(f)) => f.iter().map(|f| f.content.size).sum(),
+        Some(request::Body::UrlEncodedForm(_)) | None => 0,
+    }
+}
+
+fn content_type(response: &Response) -> Option<String> {
+    match &response.content {
+        Some(c) => Some(c.content_type.essence_str().to_string()),
+        None => response
+            .headers
+            .content_type()
+            .ok()
+            .flatten()
+            .and_then(|t| t.parse::<mime::Mime>().ok())
+            .map(|m| m.essence_str().to_string()),
+    }
+}
+
+fn any_match(patterns: &[regex::Regex], s: &str) -> bool {
+    patterns.iter().any(|p| p.is_match(s))
+}
+
+#[derive(Debug, Clone)]
+pub struct Filter {
+    hosts: Vec<regex::Regex>,
+    ignore_hosts: Vec<regex::Regex>,
+    path_prefixes: Vec<String>,
+    path_regexes: Vec<regex::Regex>,
+    methods: Vec<String>,
+    status: Vec<(u16, u16)>,
+    content_types: Vec<regex::Regex>,
+    ignore_content_types: Vec<regex::Regex>,
+    max_body_size: Option<u64>,
+}
+impl Filter {
+    pub fn new(config: &FilterConfig) -> Result<Self, InvalidFilter> {
+        Ok(Self {
+            hosts: globs(&config.hosts)?,
+            ignore_hosts: globs(&config.ignore_hosts)?,
+            path_prefixes: config.path_prefixes.clone(),
+            path_regexes: config
+                .path_regexes
+                .iter()
+                .map(|r| {
+                    regex::Regex::new(r.as_str()).map_err(|e| InvalidFilter::Pattern(r.clone(), e))
+                })
+                .collect::<Result<_, _>>()?,
+            methods: config.methods.iter().map(|m| m.to_uppercase()).collect(),
+            status: config
+                .status
+                .iter()
+                .map(|s| status_range(s.as_str()))
+                .collect::<Result<_, _>>()?,
+            content_types: globs(&config.content_types)?,
+            ignore_content_types: globs(&config.ignore_content_types)?,
+            max_body_size: config.max_body_size,
+        })
+    }
+    /// Checks the parts of the filter that only depend on the request.
+    pub fn matches_request(&self, request: &Request) -> bool {
+        let host = request
+            .url
+            .host
+            .as_ref()
+            .map(|h| h.to_string())
+            .unwrap_or_default();
+        let path = request.url.path.as_str();
+        (self.hosts.is_empty() || any_match(&self.hosts, host.as_str()))
+            && !any_match(&self.ignore_hosts, host.as_str())
+            && (self.path_prefixes.is_empty()
+                || self
+                    .path_prefixes
+                    .iter()
+                    .any(|p| path.starts_with(p.as_str())))
+            && (self.path_regexes.is_empty() || any_match(&self.path_regexes, path))
+            && (self.methods.is_empty()
+                || self
+                    .methods
+                    .iter()
+                    .any(|m| m.as_str() == request.method.as_str()))
+            && self.max_body_size.is_none_or(|m| body_size(request) <= m)
+    }
+    pub fn matches_response(&self, response: &Response) -> bool {
+        let status = response.status_code.0;
+        let content_type = content_type(response);
+        (self.status.is_empty()
+            || self
+                .status
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&status)))
+            && (self.content_types.is_empty()
+                || content_type
+                    .as_ref()
+                    .is_some_and(|t| any_match(&self.content_types, t.as_str())))
+            && !content_type
+                .as_ref()
+                .is_some_and(|t| any_match(&self.ignore_content_types, t.as_str()))
+            && self
+                .max_body_size
+                .is_none_or(|m| response.content.as_ref().map_or(0, |c| c.size) <= m)
+    }
+    pub fn matches(&self, entry: &Entry) -> bool {
+        self.matches_request(&entry.request)
+            && match &entry.response {
+                Some(r) => self.matches_response(r),
+                // failed flows have no status or content type to select
+                None => self.status.is_empty() && self.content_types.is_empty(),
+            }
+    }
+}
@@ -0,0 +1,792 @@
+use crate::{
+    archive::{
+        self,
+        writer::{write_recording, WriteError, WriteOptions},
+    },
+    content::Payload,
+    header, request, response, websocket,
+};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{io, net::SocketAddr, path::Path};
+
+pub const HAR_VERSION: &str = "1.2";
+const BASE64: &str = "base64";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Creator {
+    pub name: String,
+    pub version: String,
+}
+impl Default for Creator {
+    fn default() -> Self {
+        Self {
+            name: String::from(env!("CARGO_PKG_NAME")),
+            version: String::from(env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_only: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secure: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub same_site: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Param {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<Param>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    // not part of HAR 1.2, marks a base64 encoded binary text
+    #[serde(default, rename = "_encoding", skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+    #[serde(default)]
+    pub headers: Vec<NameValue>,
+    #[serde(default)]
+    pub query_string: Vec<NameValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    pub size: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<i64>,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    pub status: u16,
+    #[serde(default)]
+    pub status_text: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+    #[serde(default)]
+    pub headers: Vec<NameValue>,
+    pub content: Content,
+    #[serde(default, rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+    /// why no response was received, as written by Chrome
+    #[serde(rename = "_error", default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cache {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect: Option<f64>,
+    #[serde(default)]
+    pub send: f64,
+    #[serde(default)]
+    pub wait: f64,
+    #[serde(default)]
+    pub receive: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssl: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pageref: Option<String>,
+    pub started_date_time: DateTime<Utc>,
+    pub time: f64,
+    pub request: Request,
+    pub response: Response,
+    #[serde(default)]
+    pub cache: Cache,
+    pub timings: Timings,
+    #[serde(
+        default,
+        rename = "serverIPAddress",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub server_ip_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<String>,
+    #[serde(
+        rename = "_webSocketMessages",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub web_socket_messages: Vec<WebSocketMessage>,
+}
+
+/// A WebSocket message as written by Chrome.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketMessage {
+    /// `send` or `receive`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// seconds since the epoch
+    pub time: f64,
+    pub opcode: u8,
+    /// base64 encoded unless `opcode` is text
+    pub data: String,
+}
+impl WebSocketMessage {
+    fn from_message(message: &websocket::WebSocketMessage) -> Self {
+        Self {
+            kind: String::from(match message.direction {
+                websocket::Direction::ClientToServer => "send",
+                websocket::Direction::ServerToClient => "receive",
+            }),
+            time: message.time.timestamp_micros() as f64 / 1_000_000.0,
+            opcode: message.opcode.code(),
+            data: match message.opcode {
+                websocket::Opcode::Text => String::from_utf8_lossy(&message.data).into_owned(),
+                _ => base64::engine::general_purpose::STANDARD.encode(&message.data),
+            },
+        }
+    }
+    fn to_message(&self) -> Result<websocket::WebSocketMessage, InvalidEntry> {
+        let opcode =
+            websocket::Opcode::from_code(self.opcode).ok_or(InvalidEntry::Opcode(self.opcode))?;
+        Ok(websocket::WebSocketMessage {
+            direction: match self.kind.as_str() {
+                "send" => websocket::Direction::ClientToServer,
+                _ => websocket::Direction::ServerToClient,
+            },
+            opcode,
+            data: match opcode {
+                websocket::Opcode::Text => self.data.as_bytes().into(),
+                _ => base64::engine::general_purpose::STANDARD
+                    .decode(self.data.as_str())
+                    .map_err(InvalidEntry::from)?
+                    .into_boxed_slice(),
+            },
+            time: DateTime::from_timestamp_micros((self.time * 1_000_000.0) as i64)
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Log {
+    pub version: String,
+    pub creator: Creator,
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Har {
+    pub log: Log,
+}
+
+fn name_values(headers: &header::Headers) -> Vec<NameValue> {
+    headers
+        .0
+        .iter()
+        .map(|h| NameValue {
+            name: h.name.as_str().to_string(),
+            value: h.value.to_string_lossy().into_owned(),
+        })
+        .collect()
+}
+fn header_mime_type(headers: &header::Headers) -> Option<String> {
+    headers
+        .get(&header::CONTENT_TYPE)
+        .map(|v| v.to_string_lossy().into_owned())
+}
+fn encode_data(data: Option<&[u8]>) -> (Option<String>, Option<String>) {
+    match data.map(std::str::from_utf8) {
+        Some(Ok(s)) => (Some(s.to_string()), None),
+        Some(Err(_)) => (
+            data.map(|d| base64::engine::general_purpose::STANDARD.encode(d)),
+            Some(String::from(BASE64)),
+        ),
+        None => (None, None),
+    }
+}
+
+fn post_data(headers: &header::Headers, body: &request::Body) -> (PostData, i64) {
+    match body {
+        request::Body::Content(c) => {
+            let (text, encoding) = encode_data(c.data.as_deref());
+            (
+                PostData {
+                    mime_type: header_mime_type(headers)
+                        .unwrap_or_else(|| c.content_type.to_string()),
+                    params: Vec::new(),
+                    text,
+                    encoding,
+                },
+                c.size as i64,
+            )
+        }
+        request::Body::UrlEncodedForm(f) => {
+            let text = request::UrlEncodedFormEntry::encode(f);
+            let size = text.len() as i64;
+            (
+                PostData {
+                    mime_type: mime::APPLICATION_WWW_FORM_URLENCODED.to_string(),
+                    params: f
+                        .iter()
+                        .map(|e| Param {
+                            name: e.name.clone(),
+                            value: Some(e.value.clone()),
+                            file_name: None,
+                            content_type: None,
+                        })
+                        .collect(),
+                    text: Some(text),
+                    encoding: None,
+                },
+                size,
+            )
+        }
+        request::Body::MultipartForm(f) => (
+            PostData {
+                mime_type: header_mime_type(headers)
+                    .unwrap_or_else(|| mime::MULTIPART_FORM_DATA.to_string()),
+                params: f
+                    .iter()
+                    .map(|e| Param {
+                        name: e.name.clone().unwrap_or_default(),
+                        value: e
+                            .content
+                            .data
+                            .as_ref()
+                            .and_then(|d| std::str::from_utf8(d).ok())
+                            .map(str::to_string),
+                        file_name: e.file_name.clone(),
+                        content_type: Some(e.content.content_type.to_string()),
+                    })
+                    .collect(),
+                text: None,
+                encoding: None,
+            },
+            f.iter().map(|e| e.content.size as i64).sum(),
+        ),
+    }
+}
+
+fn same_site(value: response::SameSite) -> String {
+    String::from(match value {
+        response::SameSite::Strict => "Strict",
+        response::SameSite::Lax => "Lax",
+        response::SameSite::None => "None",
+    })
+}
+fn response_cookie(cookie: &response::Cookie, start_time: DateTime<Utc>) -> Cookie {
+    Cookie {
+        name: cookie.name.clone(),
+        value: cookie.value.clone(),
+        path: cookie.path.clone(),
+        domain: cookie.domain.clone(),
+        expires: match &cookie.expires {
+            Some(response::Expiration::DateTime(d)) => Some(*d),
+            Some(response::Expiration::Session) => None,
+            None => cookie
+                .max_age
+                .and_then(|a| chrono::Duration::from_std(a).ok())
+                .map(|a| start_time + a),
+        },
+        http_only: cookie.http_only,
+        secure: cookie.secure,
+        same_site: cookie.same_site.map(same_site),
+    }
+}
+
+fn duration_ms(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_microseconds().unwrap_or(0).max(0) as f64 / 1000.0
+}
+
+impl Response {
+    fn from_response(response: &response::Response, start_time: DateTime<Utc>) -> Self {
+        let content = match &response.content {
+            Some(c) => {
+                let (text, encoding) = encode_data(c.data.as_deref());
+                Content {
+                    size: c.size as i64,
+                    compression: c
+                        .encoding
+                        .as_ref()
+                        .filter(|e| e.decoded)
+                        .and_then(|e| e.encoded_size)
+                        .map(|s| c.size as i64 - s as i64),
+                    mime_type: header_mime_type(&response.headers)
+                        .unwrap_or_else(|| c.content_type.to_string()),
+                    text,
+                    encoding,
+                }
+            }
+            None => Content {
+                size: 0,
+                compression: None,
+                mime_type: header_mime_type(&response.headers).unwrap_or_default(),
+                text: None,
+                encoding: None,
+            },
+        };
+        Self {
+            status: response.status_code.0,
+            status_text: http::StatusCode::from_u16(response.status_code.0)
+                .ok()
+                .and_then(|s| s.canonical_reason())
+                .unwrap_or_default()
+                .to_string(),
+            http_version: response.http_version.as_str().to_string(),
+            cookies: response
+                .cookies
+                .0
+                .iter()
+                .map(|c| response_cookie(c, start_time))
+                .collect(),
+            headers: name_values(&response.headers),
+            body_size: response
+                .content
+                .as_ref()
+                .and_then(|c| c.encoding.as_ref())
+                .and_then(|e| e.encoded_size)
+                .map_or(content.size, |s| s as i64),
+            content,
+            redirect_url: response
+                .headers
+                .get(&header::LOCATION)
+                .map(|v| v.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            headers_size: -1,
+            error: None,
+        }
+    }
+}
+
+impl Timings {
+    fn from_phases(phases: &crate::Phases) -> Self {
+        Self {
+            blocked: None,
+            dns: phases.dns,
+            // HAR counts the TLS handshake as part of connecting
+            connect: phases.connect.map(|c| c + phases.tls.unwrap_or_default()),
+            send: phases.send.unwrap_or_default(),
+            wait: phases.wait.unwrap_or_default(),
+            receive: phases.receive.unwrap_or_default(),
+            ssl: phases.tls,
+        }
+    }
+    // -1 marks a phase that does not apply
+    fn to_phases(&self) -> crate::Phases {
+        let known = |t: Option<f64>| t.filter(|t| *t >= 0.0);
+        let ssl = known(self.ssl);
+        crate::Phases {
+            dns: known(self.dns),
+            connect: known(self.connect).map(|c| (c - ssl.unwrap_or_default()).max(0.0)),
+            tls: ssl,
+            send: known(Some(self.send)),
+            wait: known(Some(self.wait)),
+            receive: known(Some(self.receive)),
+        }
+    }
+}
+
+impl Entry {
+    pub fn from_entry(entry: &crate::Entry) -> Self {
+        let start_time = entry.timings.start_time;
+        let time = duration_ms(start_time, entry.timings.finish_time);
+        let request = &entry.request;
+        let (post_data, request_body_size) = match &request.body {
+            Some(b) => {
+                let (p, s) = post_data(&request.headers, b);
+                (Some(p), s)
+            }
+            None => (None, 0),
+        };
+        let response = match &entry.response {
+            Some(r) => Response::from_response(r, start_time),
+            None => Response {
+                status: 0,
+                status_text: String::new(),
+                http_version: String::new(),
+                cookies: Vec::new(),
+                headers: Vec::new(),
+                content: Content {
+                    size: 0,
+                    compression: None,
+                    mime_type: String::from("x-unknown"),
+                    text: None,
+                    encoding: None,
+                },
+                redirect_url: String::new(),
+                headers_size: -1,
+                body_size: -1,
+                error: Some(
+                    entry
+                        .error
+                        .as_ref()
+                        .map_or_else(|| String::from("no response"), |e| e.message.clone()),
+                ),
+            },
+        };
+        Self {
+            pageref: None,
+            started_date_time: start_time,
+            time,
+            request: Request {
+                method: request.method.as_str().to_string(),
+                url: request.url.url.to_string(),
+                http_version: request.http_version.as_str().to_string(),
+                cookies: request
+                    .cookies
+                    .0
+                    .iter()
+                    .map(|c| Cookie {
+                        name: c.name.clone(),
+                        value: c.value.clone(),
+                        path: None,
+                        domain: None,
+                        expires: None,
+                        http_only: None,
+                        secure: None,
+                        same_site: None,
+                    })
+                    .collect(),
+                headers: name_values(&request.headers),
+                query_string: request
+                    .url
+                    .query
+                    .iter()
+                    .map(|q| NameValue {
+                        name: q.name.clone(),
+                        value: q.value.clone(),
+                    })
+                    .collect(),
+                post_data,
+                headers_size: -1,
+                body_size: request_body_size,
+            },
+            response,
+            cache: Cache {},
+            timings: match &entry.timings.phases {
+                Some(p) => Timings::from_phases(p),
+                None => Timings {
+                    blocked: None,
+                    dns: None,
+                    connect: None,
+                    send: 0.0,
+                    wait: time,
+                    receive: 0.0,
+                    ssl: None,
+                },
+            },
+            server_ip_address: entry.server_addr.map(|a| a.ip().to_string()),
+            connection: None,
+            web_socket_messages: entry.websocket.as_ref().map_or_else(Vec::new, |w| {
+                w.messages
+                    .iter()
+                    .map(WebSocketMessage::from_message)
+                    .collect()
+            }),
+        }
+    }
+}
+
+// Writes the log object piece by piece so that entries never have to be
+// collected in memory.
+pub struct HarWriter<W: io::Write> {
+    writer: W,
+    first: bool,
+}
+impl<W: io::Write> HarWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(b"{\"log\":{\"version\":")?;
+        serde_json::to_writer(&mut writer, HAR_VERSION)?;
+        writer.write_all(b",\"creator\":")?;
+        serde_json::to_writer(&mut writer, &Creator::default())?;
+        writer.write_all(b",\"entries\":[")?;
+        Ok(Self {
+            writer,
+            first: true,
+        })
+    }
+    pub fn write_entry(&mut self, entry: &crate::Entry) -> io::Result<()> {
+        if !self.first {
+            self.writer.write_all(b",")?;
+        }
+        self.first = false;
+        serde_json::to_writer(&mut self.writer, &Entry::from_entry(entry))?;
+        Ok(())
+    }
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(b"]}}")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidEntry {
+    #[error("invalid base64 data")]
+    Base64(
+        #[source]
+        #[from]
+        base64::DecodeError,
+    ),
+    #[error("invalid request")]
+    Request(
+        #[source]
+        #[from]
+        request::InvalidRequest,
+    ),
+    #[error("invalid response")]
+    Response(
+        #[source]
+        #[from]
+        response::InvalidResponse,
+    ),
+    #[error("invalid server ip address")]
+    ServerAddr(
+        #[source]
+        #[from]
+        std::net::AddrParseError,
+    ),
+    #[error("invalid websocket opcode {0}")]
+    Opcode(u8),
+}
+
+fn http_version(version: &str) -> String {
+    match version.to_ascii_lowercase().as_str() {
+        "" => String::from(crate::HttpVersion::Http11.as_str()),
+        "h2" | "http/2" | "http/2.0" => String::from(crate::HttpVersion::H2.as_str()),
+        "h3" | "http/3" | "http/3.0" => String::from(crate::HttpVersion::H3.as_str()),
+        v => v.to_ascii_uppercase(),
+    }
+}
+fn decode_data(
+    text: Option<&str>,
+    encoding: Option<&str>,
+) -> Result<Option<Vec<u8>>, base64::DecodeError> {
+    Ok(match (text, encoding) {
+        (Some(t), Some(BASE64)) => Some(base64::engine::general_purpose::STANDARD.decode(t)?),
+        (Some(t), _) => Some(t.as_bytes().to_vec()),
+        (None, _) => None,
+    })
+}
+// HTTP/2 pseudo headers exported by browsers are not valid header names
+fn header_pairs(headers: &[NameValue]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    headers
+        .iter()
+        .filter(|h| !h.name.starts_with(':'))
+        .map(|h| (h.name.as_bytes(), h.value.as_bytes()))
+}
+impl PostData {
+    fn to_bytes(&self) -> Result<Option<Vec<u8>>, base64::DecodeError> {
+        if self.text.is_none()
+            && !self.params.is_empty()
+            && self
+                .mime_type
+                .starts_with(mime::APPLICATION_WWW_FORM_URLENCODED.as_ref())
+        {
+            return Ok(Some(
+                url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(
+                        self.params
+                            .iter()
+                            .map(|p| (p.name.as_str(), p.value.as_deref().unwrap_or_default())),
+                    )
+                    .finish()
+                    .into_bytes(),
+            ));
+        }
+        decode_data(self.text.as_deref(), self.encoding.as_deref())
+    }
+}
+impl Entry {
+    pub fn to_entry(&self, index: u32) -> Result<crate::Entry, InvalidEntry> {
+        let request_body = match &self.request.post_data {
+            Some(p) => p.to_bytes().map_err(InvalidEntry::from)?,
+            None => None,
+        };
+        let request = crate::Request::parse(
+            http_version(&self.request.http_version).as_str(),
+            self.request.method.as_str(),
+            self.request.url.as_str(),
+            header_pairs(&self.request.headers),
+            request_body.as_deref().map(Payload::Decoded),
+        )
+        .map_err(InvalidEntry::from)?;
+        let finish_time = self.started_date_time
+            + chrono::Duration::microseconds((self.time.max(0.0) * 1000.0) as i64);
+        // browsers export requests that never got a response with status 0
+        let (response, error) = if self.response.status == 0 {
+            let error = crate::FlowError {
+                message: self
+                    .response
+                    .error
+                    .clone()
+                    .unwrap_or_else(|| String::from("no response")),
+                time: finish_time,
+            };
+            (None, Some(error))
+        } else {
+            let response_body = decode_data(
+                self.response.content.text.as_deref(),
+                self.response.content.encoding.as_deref(),
+            )
+            .map_err(InvalidEntry::from)?;
+            let response = crate::Response::parse(
+                http_version(&self.response.http_version).as_str(),
+                self.response.status,
+                self.request.url.as_str(),
+                header_pairs(&self.response.headers),
+                response_body.as_deref().map(Payload::Decoded),
+            )
+            .map_err(InvalidEntry::from)?;
+            (Some(response), None)
+        };
+        let server_addr = match self.server_ip_address.as_deref() {
+            Some(a) if !a.is_empty() => Some(SocketAddr::new(
+                a.trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse()
+                    .map_err(InvalidEntry::from)?,
+                request.url.url.port_or_known_default().unwrap_or_default(),
+            )),
+            _ => None,
+        };
+        Ok(crate::Entry {
+            version: crate::VERSION,
+            index,
+            // HAR does not record the client side of the connection
+            client_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
+            server_addr,
+            client_tls: None,
+            server_tls: None,
+            timings: crate::Timings {
+                start_time: self.started_date_time,
+                finish_time,
+                phases: Some(self.timings.to_phases()),
+            },
+            request,
+            response,
+            error,
+            // HAR has no close frame details, only the messages
+            websocket: if self.web_socket_messages.is_empty() {
+                None
+            } else {
+                Some(websocket::WebSocket {
+                    messages: self
+                        .web_socket_messages
+                        .iter()
+                        .map(WebSocketMessage::to_message)
+                        .collect::<Result<_, _>>()?,
+                    ..Default::default()
+                })
+            },
+            redactions: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("failed to parse har")]
+    Parse(
+        #[source]
+        #[from]
+        serde_json::Error,
+    ),
+    #[error("invalid har entry {index}")]
+    Entry {
+        index: usize,
+        #[source]
+        source: InvalidEntry,
+    },
+    #[error("failed to write recording")]
+    Write(
+        #[source]
+        #[from]
+        WriteError,
+    ),
+}
+
+// Converts every entry of the HAR log, ordered by start time, and writes them
+// as a recording at `dest`. Returns None when the log has no entries. The
+// entries before an invalid one are kept as a finished recording.
+pub fn import<R: io::Read, P: AsRef<Path>>(
+    reader: R,
+    dest: P,
+    options: WriteOptions,
+) -> Result<Option<archive::Info>, ImportError> {
+    let mut har: Har =
+        serde_json::from_reader(io::BufReader::new(reader)).map_err(ImportError::from)?;
+    har.log.entries.sort_by_key(|e| e.started_date_time);
+    write_recording(dest.as_ref(), options, |write| {
+        for (index, e) in har.log.entries.iter().enumerate() {
+            let entry = e
+                .to_entry(index as u32)
+                .map_err(|source| ImportError::Entry { index, source })?;
+            write(&entry).map_err(ImportError::from)?;
+        }
+        Ok(())
+    })
+}
@@ -12,6 +12,18 @@ pub enum HeaderValue {
 #[error(transparent)]
 pub struct InvalidHeaderValue(http::header::InvalidHeaderValue);
 impl HeaderValue {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Text(t) => t.as_bytes(),
+            Self::Binary(b) => b,
+        }
+    }
+    pub fn to_string_lossy(&self) -> std::borrow::Cow<'_, str> {
+        match self {
+            Self::Text(t) => std::borrow::Cow::Borrowed(t.as_str()),
+            Self::Binary(b) => String::from_utf8_lossy(b),
+        }
+    }
     pub fn parse(value: &[u8]) -> Result<Self, InvalidHeaderValue> {
         let v = http::HeaderValue::from_bytes(value).map_err(InvalidHeaderValue)?;
         Ok(match v.to_str() {
@@ -66,6 +78,9 @@ impl Headers {
         }
         Ok(Self(ret))
     }
+    pub fn get(&self, name: &HeaderName) -> Option<&HeaderValue> {
+        self.0.iter().find(|h| &h.name == name).map(|h| &h.value)
+    }
     pub(crate) fn content_type(&self) -> Result<Option<&str>, ()> {
         Ok(match self.0.iter().find(|v| v.name == CONTENT_TYPE) {
             Some(v) => match &v.value {
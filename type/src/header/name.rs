@@ -13,6 +13,11 @@ macro_rules! std_header_names {
                     _ => None
                 }
             }
+            pub const fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$i => $name,)+
+                }
+            }
         }
         $(pub const $upper: HeaderName = HeaderName::Standard(StandardHeader::$i);)+
     };
@@ -552,6 +557,12 @@ impl HeaderName {
                 .as_str(),
         ))
     }
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Standard(s) => s.as_str(),
+            Self::Custom(c) => c,
+        }
+    }
     pub fn from_lower(data: &str) -> Self {
         match StandardHeader::from_lower(data) {
             Some(v) => Self::Standard(v),
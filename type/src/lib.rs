@@ -19,6 +19,17 @@ impl Display for HttpVersionParseErr {
 }
 impl error::Error for HttpVersionParseErr {}
 
+impl HttpVersion {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http09 => "HTTP/0.9",
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 => "HTTP/1.1",
+            Self::H2 => "HTTP/2.0",
+            Self::H3 => "HTTP/3.0",
+        }
+    }
+}
 impl FromStr for HttpVersion {
     type Err = HttpVersionParseErr;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -46,6 +57,22 @@ pub enum Method {
     Trace,
     Extension(Box<str>),
 }
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Get => "GET",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Head => "HEAD",
+            Self::Options => "OPTIONS",
+            Self::Connect => "CONNECT",
+            Self::Patch => "PATCH",
+            Self::Trace => "TRACE",
+            Self::Extension(v) => v,
+        }
+    }
+}
 impl FromStr for Method {
     type Err = std::convert::Infallible;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -85,6 +112,28 @@ pub use request::Request;
 pub mod response;
 pub use response::Response;
 
+pub mod archive;
+
+pub mod har;
+
+pub mod mitmproxy;
+
+pub mod filter;
+
+pub mod redact;
+
+pub mod snippet;
+
+pub mod spill;
+
+pub mod sse;
+
+pub mod tls;
+
+pub mod warc;
+
+pub mod websocket;
+
 mod serde_date_time {
     use chrono::{DateTime, Utc};
     use serde::{Deserialize, Deserializer, Serializer};
@@ -114,12 +163,36 @@ mod serde_date_time {
     }
 }
 
+/// Durations of the phases of an exchange in milliseconds, as in HAR.
+///
+/// A phase is `None` when it did not happen or is unknown, e.g. `connect`
+/// and `tls` on a reused connection. Unlike HAR, `connect` does not include
+/// `tls`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Phases {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive: Option<f64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Timings {
     #[serde(with = "serde_date_time")]
     pub start_time: chrono::DateTime<chrono::Utc>,
     #[serde(with = "serde_date_time")]
     pub finish_time: chrono::DateTime<chrono::Utc>,
+    /// `None` for entries recorded without a breakdown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phases: Option<Phases>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -127,7 +200,19 @@ pub struct Version {
     pub major: u16,
     pub minor: u16,
 }
-pub const VERSION: Version = Version { major: 0, minor: 1 };
+/// Schema of the entries written by this crate. 0.2 made the response
+/// optional and added the flow error, TLS, WebSocket and redaction fields;
+/// 0.1 entries still read as 0.2 ones without them.
+pub const VERSION: Version = Version { major: 0, minor: 2 };
+
+/// Why a flow ended without a complete response, e.g. a timeout, a reset
+/// connection or a failed TLS handshake.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowError {
+    pub message: String,
+    #[serde(with = "serde_date_time")]
+    pub time: chrono::DateTime<chrono::Utc>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Entry {
@@ -135,9 +220,54 @@ pub struct Entry {
     pub index: u32,
     pub client_addr: SocketAddr,
     pub server_addr: Option<SocketAddr>,
+    /// `None` for plain text connections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_tls: Option<tls::Tls>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tls: Option<tls::Tls>,
     pub timings: Timings,
     pub request: request::Request,
-    pub response: response::Response,
+    /// `None` for flows that failed before a response was received
+    pub response: Option<response::Response>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<FlowError>,
+    /// set if the response upgraded the connection to WebSocket
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub websocket: Option<websocket::WebSocket>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redactions: Vec<redact::Redaction>,
+}
+// Exports only need ids that are unique per record, deriving them from what
+// the record holds keeps exporting twice reproducible.
+pub(crate) fn derived_uuid(parts: &[&[u8]]) -> String {
+    use sha2::Digest;
+    let mut hasher = sha2::Sha256::new();
+    for p in parts {
+        hasher.update(p);
+    }
+    let mut h = hasher.finalize();
+    // uuid version 4 and variant bits
+    h[6] = (h[6] & 0x0f) | 0x40;
+    h[8] = (h[8] & 0x3f) | 0x80;
+    let h = hex::encode(&h[..16]);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &h[..8],
+        &h[8..12],
+        &h[12..16],
+        &h[16..20],
+        &h[20..]
+    )
+}
+impl Entry {
+    pub(crate) fn uuid(&self, kind: &str) -> String {
+        derived_uuid(&[
+            kind.as_bytes(),
+            &self.index.to_be_bytes(),
+            self.timings.start_time.to_rfc3339().as_bytes(),
+            self.client_addr.to_string().as_bytes(),
+        ])
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -179,7 +309,15 @@ impl<T: Default> Entries<T> {
             request::Body::MultipartForm(f) => f.iter().map(|f| f.content.size).sum(),
             request::Body::UrlEncodedForm(_) => 0,
         });
-        self.body_size.response += entry.response.content.as_ref().map_or(0, |r| r.size);
+        self.body_size.response += entry
+            .response
+            .as_ref()
+            .and_then(|r| r.content.as_ref())
+            .map_or(0, |r| r.size);
+        if let Some(ws) = &entry.websocket {
+            self.body_size.request += ws.size(websocket::Direction::ClientToServer);
+            self.body_size.response += ws.size(websocket::Direction::ServerToClient);
+        }
     }
     pub const fn content_size(&self) -> u64 {
         self.body_size.request + self.body_size.response
@@ -0,0 +1,488 @@
+//! Conversion of entries to mitmproxy flow dumps, the files written by
+//! `mitmdump -w` and read back by every mitmproxy tool.
+//!
+//! A dump is a plain sequence of tnetstring encoded `HTTPFlow` states. Flows
+//! are written in the state layout of [`FLOW_FORMAT_VERSION`] (mitmproxy 7),
+//! which later mitmproxy releases migrate when loading, and read back from
+//! any version since.
+
+use crate::{
+    archive::{
+        self,
+        writer::{write_recording, WriteError, WriteOptions},
+    },
+    content::ContentCoding,
+    header::{self, Headers},
+    request, HttpVersion,
+};
+use chrono::{DateTime, Utc};
+use std::{borrow::Cow, io, net::SocketAddr, path::Path};
+
+pub mod flow;
+
+pub mod tnetstring;
+use tnetstring::Value;
+
+pub const FLOW_FORMAT_VERSION: i64 = 14;
+/// `ConnectionState.CLOSED`
+const CONNECTION_CLOSED: i64 = 0;
+
+fn dict<const N: usize>(items: [(&str, Value); N]) -> Value {
+    Value::Dict(items.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+fn timestamp(t: DateTime<Utc>) -> f64 {
+    t.timestamp() as f64 + f64::from(t.timestamp_subsec_nanos()) / 1e9
+}
+fn address(host: String, port: u16) -> Value {
+    Value::List(vec![Value::String(host), Value::Int(port.into())])
+}
+fn socket_address(addr: SocketAddr) -> Value {
+    address(addr.ip().to_string(), addr.port())
+}
+
+// Points in time of the flow rebuilt from the phases, the inverse of how the
+// addon derives phases from mitmproxy timestamps.
+struct Timestamps {
+    start: f64,
+    finish: f64,
+    request_end: f64,
+    /// start and end of the TCP handshake when the flow opened the connection
+    connect: Option<(f64, f64)>,
+    server_tls_setup: Option<f64>,
+    response_start: f64,
+}
+impl Timestamps {
+    fn new(entry: &crate::Entry) -> Self {
+        let start = timestamp(entry.timings.start_time);
+        let finish = timestamp(entry.timings.finish_time);
+        let phases = entry.timings.phases.as_ref();
+        let secs = |ms: Option<f64>| ms.unwrap_or_default() / 1000_f64;
+        let request_end = start + secs(phases.and_then(|p| p.send));
+        let response_start = finish - secs(phases.and_then(|p| p.receive));
+        // mitmproxy resolves names while connecting
+        let handshake = phases.and_then(|p| p.connect).map(|connect| {
+            (
+                secs(phases.and_then(|p| p.dns)) + connect / 1000_f64,
+                match entry.server_tls {
+                    Some(_) => secs(phases.and_then(|p| p.tls)),
+                    None => 0_f64,
+                },
+            )
+        });
+        // the handshake ends when waiting for the response starts
+        let ready = match phases.and_then(|p| p.wait) {
+            Some(wait) => response_start - wait / 1000_f64,
+            None => request_end + handshake.map_or(0_f64, |(connect, tls)| connect + tls),
+        };
+        let connect = handshake.map(|(connect, tls)| {
+            let tcp = ready - tls;
+            ((tcp - connect).max(start), tcp)
+        });
+        let server_tls_setup = entry.server_tls.as_ref().map(|_| match connect {
+            Some(_) => ready,
+            None => start,
+        });
+        Self {
+            start,
+            finish,
+            request_end,
+            connect,
+            server_tls_setup,
+            response_start,
+        }
+    }
+}
+
+fn client_conn(entry: &crate::Entry, t: &Timestamps) -> Value {
+    let tls = entry.client_tls.as_ref();
+    dict([
+        ("id", Value::String(entry.uuid("client"))),
+        ("address", socket_address(entry.client_addr)),
+        ("sockname", address(String::from("0.0.0.0"), 0)),
+        ("state", Value::Int(CONNECTION_CLOSED)),
+        ("error", Value::Null),
+        ("tls", Value::Bool(tls.is_some())),
+        ("tls_established", Value::Bool(tls.is_some())),
+        ("tls_extensions", Value::List(Vec::new())),
+        ("tls_version", tls.and_then(|t| t.version.clone()).into()),
+        ("cipher_name", tls.and_then(|t| t.cipher.clone()).into()),
+        ("cipher_list", Value::List(Vec::new())),
+        (
+            "alpn",
+            tls.and_then(|t| t.alpn.as_ref())
+                .map(|a| a.as_bytes())
+                .into(),
+        ),
+        ("alpn_offers", Value::List(Vec::new())),
+        ("sni", tls.and_then(|t| t.sni.clone()).into()),
+        // certificates are kept as summaries, without their DER encoding
+        ("certificate_list", Value::List(Vec::new())),
+        ("mitmcert", Value::Null),
+        ("timestamp_start", Value::Float(t.start)),
+        ("timestamp_tls_setup", tls.map(|_| t.start).into()),
+        ("timestamp_end", Value::Float(t.finish)),
+    ])
+}
+
+fn server_conn(entry: &crate::Entry, t: &Timestamps) -> Value {
+    let tls = entry.server_tls.as_ref();
+    let url = &entry.request.url.url;
+    dict([
+        ("id", Value::String(entry.uuid("server"))),
+        (
+            "address",
+            address(
+                entry
+                    .request
+                    .url
+                    .host
+                    .as_ref()
+                    .map_or_else(String::new, ToString::to_string),
+                url.port_or_known_default().unwrap_or_default(),
+            ),
+        ),
+        ("ip_address", entry.server_addr.map(socket_address).into()),
+        ("source_address", Value::Null),
+        ("state", Value::Int(CONNECTION_CLOSED)),
+        ("error", Value::Null),
+        ("tls", Value::Bool(tls.is_some())),
+        ("tls_established", Value::Bool(tls.is_some())),
+        ("tls_version", tls.and_then(|t| t.version.clone()).into()),
+        ("cipher_name", tls.and_then(|t| t.cipher.clone()).into()),
+        ("cipher_list", Value::List(Vec::new())),
+        (
+            "alpn",
+            tls.and_then(|t| t.alpn.as_ref())
+                .map(|a| a.as_bytes())
+                .into(),
+        ),
+        ("alpn_offers", Value::List(Vec::new())),
+        ("sni", tls.and_then(|t| t.sni.clone()).into()),
+        ("certificate_list", Value::List(Vec::new())),
+        ("via", Value::Null),
+        ("via2", Value::Null),
+        ("timestamp_start", t.connect.map(|(start, _)| start).into()),
+        ("timestamp_tcp_setup", t.connect.map(|(_, tcp)| tcp).into()),
+        ("timestamp_tls_setup", t.server_tls_setup.into()),
+        ("timestamp_end", entry.server_addr.map(|_| t.finish).into()),
+    ])
+}
+
+// HTTP/2 and HTTP/3 require lower case field names
+fn header_list(http_version: HttpVersion, headers: &Headers, body: &Body) -> Value {
+    let lower = matches!(http_version, HttpVersion::H2 | HttpVersion::H3);
+    Value::List(
+        headers
+            .0
+            .iter()
+            .filter(|h| !(body.drop_encoding && h.name == header::CONTENT_ENCODING))
+            .map(|h| {
+                let name = match lower {
+                    true => h.name.as_str().to_ascii_lowercase(),
+                    false => h.name.as_str().to_string(),
+                };
+                let value = match (&h.name, &body.data) {
+                    (&header::CONTENT_LENGTH, Some(d)) => d.len().to_string().into_bytes(),
+                    _ => h.value.as_bytes().to_vec(),
+                };
+                Value::List(vec![Value::Bytes(name.into_bytes()), Value::Bytes(value)])
+            })
+            .collect(),
+    )
+}
+
+// mitmproxy keeps bodies as transferred, so decoded data gets its
+// Content-Encoding applied again. When that is not possible the header is
+// dropped instead.
+struct Body {
+    data: Option<Vec<u8>>,
+    drop_encoding: bool,
+}
+impl Body {
+    /// `None` data stands for a body that was not recorded
+    fn encode(data: Option<Cow<'_, [u8]>>, codings: &[ContentCoding]) -> Self {
+        match data {
+            Some(d) if !codings.is_empty() => match ContentCoding::encode_all(codings, &d) {
+                Ok(e) => Self {
+                    data: Some(e),
+                    drop_encoding: false,
+                },
+                Err(_) => Self {
+                    data: Some(d.into_owned()),
+                    drop_encoding: true,
+                },
+            },
+            d => Self {
+                data: d.map(Cow::into_owned),
+                drop_encoding: false,
+            },
+        }
+    }
+    fn content(content: Option<&crate::content::Content>) -> Self {
+        match content {
+            None => Self::encode(Some(Cow::Borrowed(&[])), &[]),
+            Some(c) => Self::encode(
+                match &c.data {
+                    Some(d) => Some(Cow::Borrowed(d)),
+                    None if c.size == 0 => Some(Cow::Borrowed(&[])),
+                    None => None,
+                },
+                match &c.encoding {
+                    Some(e) if e.decoded => &e.codings,
+                    _ => &[],
+                },
+            ),
+        }
+    }
+    fn request(request: &request::Request) -> Self {
+        let codings = match &request.form_encoding {
+            Some(e) => e.codings.as_slice(),
+            None => &[],
+        };
+        match &request.body {
+            Some(request::Body::Content(c)) => Self::content(Some(c)),
+            None => Self::content(None),
+            Some(request::Body::UrlEncodedForm(f)) => Self::encode(
+                Some(Cow::Owned(
+                    request::UrlEncodedFormEntry::encode(f).into_bytes(),
+                )),
+                codings,
+            ),
+            Some(request::Body::MultipartForm(f)) => Self::encode(
+                request::MultipartFormEntry::encode(f, &request.headers).map(Cow::Owned),
+                codings,
+            ),
+        }
+    }
+}
+
+fn request(entry: &crate::Entry, t: &Timestamps) -> Value {
+    let request = &entry.request;
+    let url = &request.url.url;
+    let body = Body::request(request);
+    let authority = match request.http_version {
+        HttpVersion::H2 | HttpVersion::H3 => match url.port() {
+            Some(p) => format!("{}:{}", url.host_str().unwrap_or_default(), p),
+            None => url.host_str().unwrap_or_default().to_string(),
+        },
+        _ => String::new(),
+    };
+    dict([
+        (
+            "http_version",
+            Value::Bytes(request.http_version.as_str().into()),
+        ),
+        ("method", Value::Bytes(request.method.as_str().into())),
+        ("scheme", Value::Bytes(url.scheme().into())),
+        (
+            "host",
+            Value::String(
+                request
+                    .url
+                    .host
+                    .as_ref()
+                    .map_or_else(String::new, ToString::to_string),
+            ),
+        ),
+        (
+            "port",
+            Value::Int(url.port_or_known_default().unwrap_or_default().into()),
+        ),
+        ("authority", Value::Bytes(authority.into_bytes())),
+        (
+            "path",
+            Value::Bytes(url[url::Position::BeforePath..url::Position::AfterQuery].into()),
+        ),
+        (
+            "headers",
+            header_list(request.http_version, &request.headers, &body),
+        ),
+        ("content", body.data.into()),
+        ("trailers", Value::Null),
+        ("timestamp_start", Value::Float(t.start)),
+        ("timestamp_end", Value::Float(t.request_end)),
+    ])
+}
+
+fn response(response: &crate::response::Response, t: &Timestamps) -> Value {
+    let body = Body::content(response.content.as_ref());
+    // HTTP/2 and HTTP/3 have no reason phrase
+    let reason = match response.http_version {
+        HttpVersion::H2 | HttpVersion::H3 => None,
+        _ => http::StatusCode::from_u16(response.status_code.0)
+            .ok()
+            .and_then(|s| s.canonical_reason()),
+    };
+    dict([
+        (
+            "http_version",
+            Value::Bytes(response.http_version.as_str().into()),
+        ),
+        ("status_code", Value::Int(response.status_code.0.into())),
+        ("reason", Value::Bytes(reason.unwrap_or_default().into())),
+        (
+            "headers",
+            header_list(response.http_version, &response.headers, &body),
+        ),
+        ("content", body.data.into()),
+        ("trailers", Value::Null),
+        ("timestamp_start", Value::Float(t.response_start)),
+        ("timestamp_end", Value::Float(t.finish)),
+    ])
+}
+
+fn websocket(websocket: &crate::websocket::WebSocket, t: &Timestamps) -> Value {
+    dict([
+        (
+            "messages",
+            Value::List(
+                websocket
+                    .messages
+                    .iter()
+                    .map(|m| {
+                        Value::List(vec![
+                            Value::Int(m.opcode.code().into()),
+                            Value::Bool(m.direction == crate::websocket::Direction::ClientToServer),
+                            Value::Bytes(m.data.to_vec()),
+                            Value::Float(timestamp(m.time)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        ("closed_by_client", websocket.closed_by_client.into()),
+        ("close_code", websocket.close_code.map(i64::from).into()),
+        ("close_reason", websocket.close_reason.clone().into()),
+        // the entry finishes with the handshake response
+        (
+            "timestamp_end",
+            Value::Float(
+                websocket
+                    .messages
+                    .iter()
+                    .map(|m| timestamp(m.time))
+                    .fold(t.finish, f64::max),
+            ),
+        ),
+    ])
+}
+
+/// The `HTTPFlow` state of an entry.
+pub fn flow(entry: &crate::Entry) -> Value {
+    let t = Timestamps::new(entry);
+    dict([
+        ("version", Value::Int(FLOW_FORMAT_VERSION)),
+        ("type", Value::from("http")),
+        ("id", Value::String(entry.uuid("flow"))),
+        ("mode", Value::from("regular")),
+        (
+            "error",
+            entry
+                .error
+                .as_ref()
+                .map(|e| {
+                    dict([
+                        ("msg", Value::String(e.message.clone())),
+                        ("timestamp", Value::Float(timestamp(e.time))),
+                    ])
+                })
+                .into(),
+        ),
+        ("client_conn", client_conn(entry, &t)),
+        ("server_conn", server_conn(entry, &t)),
+        ("request", request(entry, &t)),
+        (
+            "response",
+            entry.response.as_ref().map(|r| response(r, &t)).into(),
+        ),
+        (
+            "websocket",
+            entry.websocket.as_ref().map(|w| websocket(w, &t)).into(),
+        ),
+        ("intercepted", Value::Bool(false)),
+        ("is_replay", Value::Null),
+        ("marked", Value::from("")),
+        ("metadata", Value::Dict(Vec::new())),
+        ("comment", Value::from("")),
+    ])
+}
+
+pub struct FlowWriter<W: io::Write> {
+    writer: W,
+}
+impl<W: io::Write> FlowWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+    pub fn write_entry(&mut self, entry: &crate::Entry) -> io::Result<()> {
+        flow(entry).write(&mut self.writer)
+    }
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the flow states of a dump one at a time.
+pub struct FlowReader<R: io::BufRead> {
+    reader: R,
+}
+impl<R: io::BufRead> FlowReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+impl<R: io::BufRead> Iterator for FlowReader<R> {
+    type Item = Result<Value, tnetstring::LoadError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        tnetstring::load(&mut self.reader).transpose()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("failed to read flow dump")]
+    Read(
+        #[source]
+        #[from]
+        tnetstring::LoadError,
+    ),
+    #[error("invalid flow {index}")]
+    Flow {
+        index: usize,
+        #[source]
+        source: flow::InvalidFlow,
+    },
+    #[error("failed to write recording")]
+    Write(
+        #[source]
+        #[from]
+        WriteError,
+    ),
+}
+
+// Converts the HTTP flows of the dump in the order they were written and
+// writes them as a recording at `dest`. Flows of other protocols are skipped.
+// Returns None when the dump has no HTTP flows. The flows before an invalid
+// one are kept as a finished recording.
+pub fn import<R: io::Read, P: AsRef<Path>>(
+    reader: R,
+    dest: P,
+    options: WriteOptions,
+) -> Result<Option<archive::Info>, ImportError> {
+    write_recording(dest.as_ref(), options, |write| {
+        let mut count = 0;
+        for (index, state) in FlowReader::new(io::BufReader::new(reader)).enumerate() {
+            let state = state.map_err(ImportError::from)?;
+            if state.get("type").and_then(Value::as_str) != Some("http") {
+                continue;
+            }
+            let entry = flow::Flow::from_state(&state)
+                .and_then(|f| f.into_entry(count))
+                .map_err(|source| ImportError::Flow { index, source })?;
+            count += 1;
+            write(&entry).map_err(ImportError::from)?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,415 @@
+//! The parts of a mitmproxy `HTTPFlow` an entry is built from. The addon
+//! fills them from live flows, [`Flow::from_state`] from the flow states of a
+//! dump.
+
+use super::tnetstring::Value;
+use crate::{content::Payload, request, response, tls, websocket, Entry, FlowError, Timings};
+use std::{borrow::Cow, net::SocketAddr};
+
+/// host and port, as mitmproxy keeps socket addresses
+pub type Address<'a> = (&'a str, u16);
+
+pub struct Request<'a> {
+    pub timestamp_start: f64,
+    pub timestamp_end: Option<f64>,
+    pub http_version: &'a str,
+    pub method: &'a str,
+    pub url: Cow<'a, str>,
+    pub headers: Vec<(&'a [u8], &'a [u8])>,
+    pub raw_content: Option<&'a [u8]>,
+}
+impl<'a> Request<'a> {
+    fn into_request(self) -> Result<request::Request, InvalidFlow> {
+        request::Request::parse(
+            self.http_version,
+            self.method,
+            &self.url,
+            self.headers.into_iter(),
+            self.raw_content.map(Payload::Raw),
+        )
+        .map_err(InvalidFlow::from)
+    }
+}
+
+pub struct Response<'a> {
+    pub timestamp_start: f64,
+    pub timestamp_end: Option<f64>,
+    pub http_version: &'a str,
+    pub status_code: u16,
+    pub headers: Vec<(&'a [u8], &'a [u8])>,
+    pub raw_content: Option<Cow<'a, [u8]>>,
+}
+impl<'a> Response<'a> {
+    fn into_response(self, url: &str) -> Result<response::Response, InvalidFlow> {
+        response::Response::parse(
+            self.http_version,
+            self.status_code,
+            url,
+            self.headers.into_iter(),
+            self.raw_content.as_deref().map(Payload::Raw),
+        )
+        .map_err(InvalidFlow::from)
+    }
+}
+
+/// The TLS attributes shared by mitmproxy's client and server connections.
+pub struct Tls<'a> {
+    pub established: bool,
+    pub version: Option<&'a str>,
+    pub cipher: Option<&'a str>,
+    pub alpn: Option<&'a [u8]>,
+    pub sni: Option<&'a str>,
+    pub certificates: Vec<tls::Certificate>,
+}
+impl<'a> Tls<'a> {
+    fn into_tls(self) -> Option<tls::Tls> {
+        self.established.then(|| tls::Tls {
+            version: self.version.map(str::to_string),
+            cipher: self.cipher.map(str::to_string),
+            alpn: self.alpn.map(|a| String::from_utf8_lossy(a).into_owned()),
+            sni: self.sni.map(str::to_string),
+            certificates: self.certificates,
+        })
+    }
+}
+
+pub struct Client<'a> {
+    pub peername: Address<'a>,
+    pub tls: Tls<'a>,
+}
+pub struct Server<'a> {
+    pub peername: Option<Address<'a>>,
+    pub timestamp_start: Option<f64>,
+    pub timestamp_tcp_setup: Option<f64>,
+    pub timestamp_tls_setup: Option<f64>,
+    pub tls: Tls<'a>,
+}
+
+pub struct WebSocketMessage<'a> {
+    pub from_client: bool,
+    pub opcode: u8,
+    pub content: &'a [u8],
+    pub timestamp: f64,
+}
+pub struct WebSocketData<'a> {
+    pub messages: Vec<WebSocketMessage<'a>>,
+    pub closed_by_client: Option<bool>,
+    pub close_code: Option<u16>,
+    pub close_reason: Option<&'a str>,
+}
+impl<'a> WebSocketData<'a> {
+    fn into_websocket(self) -> Result<websocket::WebSocket, InvalidFlow> {
+        Ok(websocket::WebSocket {
+            close_code: self.close_code,
+            close_reason: self.close_reason.map(str::to_string),
+            closed_by_client: self.closed_by_client,
+            messages: self
+                .messages
+                .into_iter()
+                .map(|m| {
+                    Ok(websocket::WebSocketMessage {
+                        direction: if m.from_client {
+                            websocket::Direction::ClientToServer
+                        } else {
+                            websocket::Direction::ServerToClient
+                        },
+                        opcode: websocket::Opcode::from_code(m.opcode)
+                            .ok_or(InvalidFlow::Opcode(m.opcode))?,
+                        data: m.content.into(),
+                        time: timestamp(m.timestamp),
+                    })
+                })
+                .collect::<Result<_, InvalidFlow>>()?,
+            segment: None,
+        })
+    }
+}
+
+pub struct Error<'a> {
+    pub msg: &'a str,
+    pub timestamp: f64,
+}
+
+pub struct Flow<'a> {
+    pub client_conn: Client<'a>,
+    pub server_conn: Server<'a>,
+    pub request: Request<'a>,
+    pub response: Option<Response<'a>>,
+    pub error: Option<Error<'a>>,
+    pub websocket: Option<WebSocketData<'a>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidFlow {
+    #[error("failed to parse request")]
+    Request(
+        #[source]
+        #[from]
+        request::InvalidRequest,
+    ),
+    #[error("failed to parse response")]
+    Response(
+        #[source]
+        #[from]
+        response::InvalidResponse,
+    ),
+    #[error("failed to parse address {0}")]
+    Address(String, #[source] std::net::AddrParseError),
+    #[error("invalid websocket opcode {0}")]
+    Opcode(u8),
+    #[error("missing or invalid flow state field {0}")]
+    State(&'static str),
+}
+
+/// Converts mitmproxy's float seconds since the epoch.
+pub fn timestamp(t: f64) -> chrono::DateTime<chrono::Utc> {
+    use chrono::TimeZone;
+    chrono::Utc.timestamp_nanos((t * 1_000_000_000_f64).round() as i64)
+}
+fn duration_ms(from: f64, to: f64) -> f64 {
+    ((to - from) * 1000_f64).max(0_f64)
+}
+fn socket_addr((host, port): Address<'_>) -> Result<SocketAddr, InvalidFlow> {
+    Ok(SocketAddr::new(
+        host.parse()
+            .map_err(|e| InvalidFlow::Address(host.to_string(), e))?,
+        port,
+    ))
+}
+
+impl<'a> Flow<'a> {
+    fn phases(&self) -> crate::Phases {
+        let request = &self.request;
+        let server = &self.server_conn;
+        // a connection opened before the request was reused, its setup
+        // belongs to an earlier flow
+        let connected = server
+            .timestamp_start
+            .filter(|t| *t >= request.timestamp_start)
+            .zip(server.timestamp_tcp_setup);
+        let tls = connected
+            .and(server.timestamp_tls_setup)
+            .zip(server.timestamp_tcp_setup)
+            .map(|(tls, tcp)| duration_ms(tcp, tls));
+        // mitmproxy usually reads the whole request before connecting, so
+        // waiting starts once both are done
+        let ready = [
+            request.timestamp_end,
+            connected.map(|(_, tcp)| tcp),
+            connected.and(server.timestamp_tls_setup),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f64::max);
+        crate::Phases {
+            // mitmproxy resolves names while connecting
+            dns: None,
+            connect: connected.map(|(start, tcp)| duration_ms(start, tcp)),
+            tls,
+            send: request
+                .timestamp_end
+                .map(|end| duration_ms(request.timestamp_start, end)),
+            wait: self
+                .response
+                .as_ref()
+                .zip(ready)
+                .map(|(r, ready)| duration_ms(ready, r.timestamp_start)),
+            receive: self.response.as_ref().and_then(|r| {
+                r.timestamp_end
+                    .map(|end| duration_ms(r.timestamp_start, end))
+            }),
+        }
+    }
+    pub fn into_entry(self, index: u32) -> Result<Entry, InvalidFlow> {
+        let finish_time = self
+            .response
+            .as_ref()
+            .and_then(|r| r.timestamp_end)
+            .or(self.error.as_ref().map(|e| e.timestamp))
+            .or(self.request.timestamp_end)
+            .unwrap_or(self.request.timestamp_start);
+        let phases = self.phases();
+        Ok(Entry {
+            version: crate::VERSION,
+            index,
+            client_addr: socket_addr(self.client_conn.peername)?,
+            server_addr: self.server_conn.peername.map(socket_addr).transpose()?,
+            client_tls: self.client_conn.tls.into_tls(),
+            server_tls: self.server_conn.tls.into_tls(),
+            timings: Timings {
+                start_time: timestamp(self.request.timestamp_start),
+                finish_time: timestamp(finish_time),
+                phases: Some(phases),
+            },
+            response: match self.response {
+                Some(r) => Some(r.into_response(&self.request.url)?),
+                None => None,
+            },
+            error: self.error.map(|e| FlowError {
+                message: e.msg.to_string(),
+                time: timestamp(e.timestamp),
+            }),
+            websocket: self.websocket.map(|w| w.into_websocket()).transpose()?,
+            request: self.request.into_request()?,
+            redactions: Vec::new(),
+        })
+    }
+}
+
+fn required<'a, T>(
+    state: &'a Value,
+    key: &'static str,
+    f: impl FnOnce(&'a Value) -> Option<T>,
+) -> Result<T, InvalidFlow> {
+    state.get(key).and_then(f).ok_or(InvalidFlow::State(key))
+}
+fn optional<'a, T>(
+    state: &'a Value,
+    key: &'static str,
+    f: impl FnOnce(&'a Value) -> Option<T>,
+) -> Result<Option<T>, InvalidFlow> {
+    match state.get(key) {
+        None => Ok(None),
+        Some(v) if v.is_null() => Ok(None),
+        Some(v) => f(v).map(Some).ok_or(InvalidFlow::State(key)),
+    }
+}
+// fields renamed by later flow format versions
+fn renamed(state: &Value, key: &'static str, old: &'static str) -> &'static str {
+    match state.get(key) {
+        Some(_) => key,
+        None => old,
+    }
+}
+fn address(value: &Value) -> Option<Address<'_>> {
+    match value.as_list()? {
+        // IPv6 addresses may carry flow info and scope id
+        [host, port, ..] => Some((host.as_str()?, port.as_i64()?.try_into().ok()?)),
+        _ => None,
+    }
+}
+fn headers(value: &Value) -> Option<Vec<(&[u8], &[u8])>> {
+    value
+        .as_list()?
+        .iter()
+        .map(|h| match h.as_list()? {
+            [name, value] => Some((name.as_bytes()?, value.as_bytes()?)),
+            _ => None,
+        })
+        .collect()
+}
+
+impl<'a> Tls<'a> {
+    /// Certificates are stored PEM encoded and are not read back.
+    fn from_state(state: &'a Value) -> Result<Self, InvalidFlow> {
+        Ok(Self {
+            established: optional(state, "timestamp_tls_setup", Value::as_f64)?.is_some()
+                || optional(state, "tls_established", Value::as_bool)? == Some(true),
+            version: optional(state, "tls_version", Value::as_str)?,
+            cipher: optional(
+                state,
+                renamed(state, "cipher", "cipher_name"),
+                Value::as_str,
+            )?,
+            alpn: optional(state, "alpn", Value::as_bytes)?,
+            // servers of older versions use `true` for the default name
+            sni: state.get("sni").and_then(Value::as_str),
+            certificates: Vec::new(),
+        })
+    }
+}
+
+impl<'a> Flow<'a> {
+    /// Reads the `HTTPFlow` state of a dump, in any flow format version
+    /// from mitmproxy 7 on.
+    pub fn from_state(state: &'a Value) -> Result<Self, InvalidFlow> {
+        let client = required(state, "client_conn", Some)?;
+        let server = required(state, "server_conn", Some)?;
+        let request = required(state, "request", Some)?;
+        let scheme = required(request, "scheme", Value::as_str)?;
+        let host = required(request, "host", Value::as_str)?;
+        let port = required(request, "port", Value::as_i64)?;
+        let path = required(request, "path", Value::as_str)?;
+        let host = match host.contains(':') {
+            true => Cow::Owned(format!("[{}]", host)),
+            false => Cow::Borrowed(host),
+        };
+        let url = match (scheme, port) {
+            ("http", 80) | ("https", 443) => format!("{}://{}{}", scheme, host, path),
+            _ => format!("{}://{}:{}{}", scheme, host, port, path),
+        };
+        Ok(Self {
+            client_conn: Client {
+                peername: required(client, renamed(client, "peername", "address"), address)?,
+                tls: Tls::from_state(client)?,
+            },
+            server_conn: Server {
+                peername: optional(server, renamed(server, "peername", "ip_address"), address)?,
+                timestamp_start: optional(server, "timestamp_start", Value::as_f64)?,
+                timestamp_tcp_setup: optional(server, "timestamp_tcp_setup", Value::as_f64)?,
+                timestamp_tls_setup: optional(server, "timestamp_tls_setup", Value::as_f64)?,
+                tls: Tls::from_state(server)?,
+            },
+            request: Request {
+                timestamp_start: required(request, "timestamp_start", Value::as_f64)?,
+                timestamp_end: optional(request, "timestamp_end", Value::as_f64)?,
+                http_version: required(request, "http_version", Value::as_str)?,
+                method: required(request, "method", Value::as_str)?,
+                url: Cow::Owned(url),
+                headers: required(request, "headers", headers)?,
+                raw_content: optional(request, "content", Value::as_bytes)?,
+            },
+            response: optional(state, "response", Some)?
+                .map(|response| -> Result<_, InvalidFlow> {
+                    Ok(Response {
+                        timestamp_start: required(response, "timestamp_start", Value::as_f64)?,
+                        timestamp_end: optional(response, "timestamp_end", Value::as_f64)?,
+                        http_version: required(response, "http_version", Value::as_str)?,
+                        status_code: required(response, "status_code", |v| {
+                            v.as_i64()?.try_into().ok()
+                        })?,
+                        headers: required(response, "headers", headers)?,
+                        raw_content: optional(response, "content", Value::as_bytes)?
+                            .map(Cow::Borrowed),
+                    })
+                })
+                .transpose()?,
+            error: optional(state, "error", Some)?
+                .map(|error| -> Result<_, InvalidFlow> {
+                    Ok(Error {
+                        msg: required(error, "msg", Value::as_str)?,
+                        timestamp: required(error, "timestamp", Value::as_f64)?,
+                    })
+                })
+                .transpose()?,
+            websocket: optional(state, "websocket", Some)?
+                .map(|websocket| -> Result<_, InvalidFlow> {
+                    Ok(WebSocketData {
+                        messages: required(websocket, "messages", |m| {
+                            m.as_list()?
+                                .iter()
+                                .map(|m| match m.as_list()? {
+                                    // later versions append injected and dropped flags
+                                    [opcode, from_client, content, timestamp, ..] => {
+                                        Some(WebSocketMessage {
+                                            from_client: from_client.as_bool()?,
+                                            opcode: opcode.as_i64()?.try_into().ok()?,
+                                            content: content.as_bytes()?,
+                                            timestamp: timestamp.as_f64()?,
+                                        })
+                                    }
+                                    _ => None,
+                                })
+                                .collect()
+                        })?,
+                        closed_by_client: optional(websocket, "closed_by_client", Value::as_bool)?,
+                        close_code: optional(websocket, "close_code", |c| {
+                            c.as_i64()?.try_into().ok()
+                        })?,
+                        close_reason: optional(websocket, "close_reason", Value::as_str)?,
+                    })
+                })
+                .transpose()?,
+        })
+    }
+}
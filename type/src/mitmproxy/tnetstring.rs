@@ -0,0 +1,279 @@
+//! The tnetstring dialect of mitmproxy: every value is encoded as
+//! `<length>:<payload><type>`, with `;` for unicode strings next to the `,`
+//! of byte strings.
+
+use std::io::{self, BufRead, Read};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Bytes(Vec<u8>),
+    String(String),
+    List(Vec<Value>),
+    /// keys are always written as unicode strings
+    Dict(Vec<(String, Value)>),
+}
+impl Value {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::Null => b'~',
+            Self::Bool(_) => b'!',
+            Self::Int(_) => b'#',
+            Self::Float(_) => b'^',
+            Self::Bytes(_) => b',',
+            Self::String(_) => b';',
+            Self::List(_) => b']',
+            Self::Dict(_) => b'}',
+        }
+    }
+    fn dump_into(&self, out: &mut Vec<u8>) {
+        let payload = match self {
+            Self::Null => Vec::new(),
+            Self::Bool(b) => (if *b { "true" } else { "false" }).into(),
+            Self::Int(i) => i.to_string().into(),
+            // Python reads the shortest representation back exactly
+            Self::Float(f) => format!("{:?}", f).into(),
+            Self::Bytes(b) => b.clone(),
+            Self::String(s) => s.as_bytes().to_vec(),
+            Self::List(l) => {
+                let mut p = Vec::new();
+                for v in l {
+                    v.dump_into(&mut p);
+                }
+                p
+            }
+            Self::Dict(d) => {
+                let mut p = Vec::new();
+                for (k, v) in d {
+                    Self::String(k.clone()).dump_into(&mut p);
+                    v.dump_into(&mut p);
+                }
+                p
+            }
+        };
+        out.extend_from_slice(payload.len().to_string().as_bytes());
+        out.push(b':');
+        out.extend_from_slice(&payload);
+        out.push(self.tag());
+    }
+    pub fn dump(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.dump_into(&mut out);
+        out
+    }
+    pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.dump())
+    }
+}
+impl Value {
+    /// The value of `key` in a dict.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Dict(d) => d.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(f) => Some(*f),
+            Self::Int(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+    // older flow formats mix up byte and unicode strings, so both accessors
+    // take either
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(b) => Some(b),
+            Self::String(s) => Some(s.as_bytes()),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Bytes(b) => std::str::from_utf8(b).ok(),
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Self::List(l) => Some(l),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("failed to read tnetstring")]
+    Read(
+        #[source]
+        #[from]
+        io::Error,
+    ),
+    #[error("invalid tnetstring: {0}")]
+    Invalid(&'static str),
+}
+
+/// Longest value read. Payloads are read as they arrive rather than allocated
+/// from their length up front, so this only bounds the digits of a length.
+const MAX_LENGTH: usize = 1 << 40;
+/// Lists and dicts nested deeper are rejected before they exhaust the stack.
+const MAX_DEPTH: usize = 256;
+
+fn pop(data: &[u8], depth: usize) -> Result<(Value, &[u8]), LoadError> {
+    let colon = data
+        .iter()
+        .position(|b| *b == b':')
+        .ok_or(LoadError::Invalid("missing length"))?;
+    let length: usize = std::str::from_utf8(&data[..colon])
+        .ok()
+        .and_then(|l| l.parse().ok())
+        .ok_or(LoadError::Invalid("invalid length"))?;
+    let data = &data[colon + 1..];
+    if data.len() <= length {
+        return Err(LoadError::Invalid("truncated value"));
+    }
+    Ok((
+        parse(&data[..length], data[length], depth)?,
+        &data[length + 1..],
+    ))
+}
+
+// `depth` is the number of lists and dicts the value is in.
+fn parse(payload: &[u8], tag: u8, depth: usize) -> Result<Value, LoadError> {
+    let text = || std::str::from_utf8(payload).map_err(|_| LoadError::Invalid("invalid utf-8"));
+    if matches!(tag, b']' | b'}') && depth >= MAX_DEPTH {
+        return Err(LoadError::Invalid("nesting too deep"));
+    }
+    Ok(match tag {
+        b'~' if payload.is_empty() => Value::Null,
+        b'!' => match payload {
+            b"true" => Value::Bool(true),
+            b"false" => Value::Bool(false),
+            _ => return Err(LoadError::Invalid("invalid bool")),
+        },
+        b'#' => Value::Int(
+            text()?
+                .parse()
+                .map_err(|_| LoadError::Invalid("invalid int"))?,
+        ),
+        b'^' => Value::Float(
+            text()?
+                .parse()
+                .map_err(|_| LoadError::Invalid("invalid float"))?,
+        ),
+        b',' => Value::Bytes(payload.to_vec()),
+        b';' => Value::String(text()?.to_string()),
+        b']' => {
+            let mut list = Vec::new();
+            let mut data = payload;
+            while !data.is_empty() {
+                let (v, rest) = pop(data, depth + 1)?;
+                list.push(v);
+                data = rest;
+            }
+            Value::List(list)
+        }
+        b'}' => {
+            let mut dict = Vec::new();
+            let mut data = payload;
+            while !data.is_empty() {
+                let (k, rest) = pop(data, depth + 1)?;
+                let (v, rest) = pop(rest, depth + 1)?;
+                dict.push((
+                    k.as_str()
+                        .ok_or(LoadError::Invalid("invalid dict key"))?
+                        .to_string(),
+                    v,
+                ));
+                data = rest;
+            }
+            Value::Dict(dict)
+        }
+        _ => return Err(LoadError::Invalid("unknown type")),
+    })
+}
+
+/// Reads the next value, `None` at the end of the input.
+pub fn load<R: BufRead>(reader: &mut R) -> Result<Option<Value>, LoadError> {
+    let mut length = Vec::new();
+    // digits of MAX_LENGTH and the colon
+    reader.take(15).read_until(b':', &mut length)?;
+    match length.pop() {
+        None => return Ok(None),
+        Some(b':') => (),
+        Some(_) => return Err(LoadError::Invalid("truncated length")),
+    }
+    let length: usize = std::str::from_utf8(&length)
+        .ok()
+        .and_then(|l| l.parse().ok())
+        .filter(|l| *l <= MAX_LENGTH)
+        .ok_or(LoadError::Invalid("invalid length"))?;
+    let mut payload = Vec::new();
+    reader.take(length as u64 + 1).read_to_end(&mut payload)?;
+    match payload.pop() {
+        Some(tag) if payload.len() == length => parse(&payload, tag, 0).map(Some),
+        _ => Err(LoadError::Invalid("truncated value")),
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(value.to_vec())
+    }
+}
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
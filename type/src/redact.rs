@@ -0,0 +1,466 @@
+use crate::{
+    content::Content,
+    header::{self, HeaderName, HeaderValue, Headers, InvalidHeaderName},
+    request, sse,
+    websocket::{Opcode, WebSocket},
+    Entry,
+};
+use serde::{Deserialize, Serialize};
+
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Location {
+    RequestHeader,
+    ResponseHeader,
+    RequestCookie,
+    ResponseCookie,
+    Query,
+    Form,
+    RequestJson,
+    ResponseJson,
+    /// data of a server-sent event
+    Event,
+    WebSocketMessage,
+}
+/// A value that was replaced by [`REDACTED`] before the entry was saved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Redaction {
+    pub location: Location,
+    /// header, cookie or parameter name, or the dotted path of a JSON field
+    pub name: String,
+}
+
+/// Masked values shorter than this are not searched for in event data and
+/// WebSocket messages, where they would match by chance.
+pub const MIN_SECRET_LEN: usize = 4;
+
+// A value masked by a header, cookie or parameter rule.
+struct Secret {
+    name: String,
+    value: Vec<u8>,
+}
+
+fn replace_all(data: &[u8], from: &[u8], to: &[u8]) -> Option<Vec<u8>> {
+    let mut ret = Vec::new();
+    let mut rest = data;
+    let mut found = false;
+    while let Some(i) = rest.windows(from.len()).position(|w| w == from) {
+        ret.extend_from_slice(&rest[..i]);
+        ret.extend_from_slice(to);
+        rest = &rest[i + from.len()..];
+        found = true;
+    }
+    ret.extend_from_slice(rest);
+    found.then_some(ret)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactConfig {
+    pub headers: Vec<String>,
+    /// cookie names, in both `Cookie` and `Set-Cookie`
+    pub cookies: Vec<String>,
+    /// query, urlencoded and multipart form parameter names
+    pub params: Vec<String>,
+    /// dotted paths into JSON bodies, `*` matches any key or array index,
+    /// e.g. `user.password` or `items.*.token`
+    pub json_paths: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    headers: Vec<HeaderName>,
+    cookies: Vec<String>,
+    params: Vec<String>,
+    json_paths: Vec<Vec<String>>,
+}
+
+fn redact_cookie_pair(pair: &str, cookies: &[String]) -> Option<String> {
+    let (name, _) = pair.split_once('=')?;
+    let name = name.trim();
+    cookies
+        .iter()
+        .any(|c| c == name)
+        .then(|| format!("{}={}", name, REDACTED))
+}
+
+fn redact_json(
+    value: &mut serde_json::Value,
+    path: &[String],
+    current: &mut Vec<String>,
+    found: &mut Vec<String>,
+) {
+    let (key, rest) = match path.split_first() {
+        Some(p) => p,
+        None => {
+            *value = serde_json::Value::String(REDACTED.to_string());
+            found.push(current.join("."));
+            return;
+        }
+    };
+    let mut visit = |k: String, v: &mut serde_json::Value| {
+        current.push(k);
+        redact_json(v, rest, current, found);
+        current.pop();
+    };
+    match value {
+        serde_json::Value::Object(o) => {
+            for (k, v) in o.iter_mut() {
+                if key == "*" || key == k {
+                    visit(k.clone(), v);
+                }
+            }
+        }
+        serde_json::Value::Array(a) => {
+            for (i, v) in a.iter_mut().enumerate() {
+                if key == "*" || key.parse() == Ok(i) {
+                    visit(i.to_string(), v);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+fn is_json(content: &Content) -> bool {
+    content.content_type.subtype() == mime::JSON
+        || content.content_type.suffix() == Some(mime::JSON)
+}
+
+impl Redactor {
+    pub fn new(config: &RedactConfig) -> Result<Self, InvalidHeaderName> {
+        Ok(Self {
+            headers: config
+                .headers
+                .iter()
+                .map(|h| HeaderName::parse(h.as_bytes()))
+                .collect::<Result<_, _>>()?,
+            cookies: config.cookies.clone(),
+            params: config.params.clone(),
+            json_paths: config
+                .json_paths
+                .iter()
+                .map(|p| p.split('.').map(str::to_string).collect())
+                .collect(),
+        })
+    }
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+            && self.cookies.is_empty()
+            && self.params.is_empty()
+            && self.json_paths.is_empty()
+    }
+    fn is_param(&self, name: &str) -> bool {
+        self.params.iter().any(|p| p == name)
+    }
+    fn redact_headers(
+        &self,
+        headers: &mut Headers,
+        location: Location,
+        redactions: &mut Vec<Redaction>,
+        secrets: &mut Vec<Secret>,
+    ) {
+        for h in headers.0.iter_mut() {
+            if self.headers.contains(&h.name) {
+                let value =
+                    std::mem::replace(&mut h.value, HeaderValue::Text(REDACTED.to_string()));
+                secrets.push(Secret {
+                    name: h.name.as_str().to_string(),
+                    value: value.as_bytes().to_vec(),
+                });
+                redactions.push(Redaction {
+                    location,
+                    name: h.name.as_str().to_string(),
+                });
+            }
+        }
+    }
+    // Cookie values also appear in the raw Cookie and Set-Cookie headers.
+    fn redact_cookie_headers(&self, headers: &mut Headers) {
+        for h in headers.0.iter_mut() {
+            let value = match &h.value {
+                HeaderValue::Text(t) => t,
+                HeaderValue::Binary(_) => continue,
+            };
+            let redacted = if h.name == header::COOKIE {
+                let pairs: Vec<_> = value.split(';').map(str::trim).collect();
+                pairs
+                    .iter()
+                    .any(|p| redact_cookie_pair(p, &self.cookies).is_some())
+                    .then(|| {
+                        pairs
+                            .iter()
+                            .map(|p| redact_cookie_pair(p, &self.cookies).unwrap_or(p.to_string()))
+                            .collect::<Vec<_>>()
+                            .join("; ")
+                    })
+            } else if h.name == header::SET_COOKIE {
+                let (pair, attributes) = match value.split_once(';') {
+                    Some((p, a)) => (p, Some(a)),
+                    None => (value.as_str(), None),
+                };
+                redact_cookie_pair(pair, &self.cookies).map(|p| match attributes {
+                    Some(a) => format!("{};{}", p, a),
+                    None => p,
+                })
+            } else {
+                None
+            };
+            if let Some(v) = redacted {
+                h.value = HeaderValue::Text(v);
+            }
+        }
+    }
+    fn redact_json(
+        &self,
+        content: &mut Content,
+        location: Location,
+        redactions: &mut Vec<Redaction>,
+    ) {
+        if self.json_paths.is_empty() || !is_json(content) {
+            return;
+        }
+        if let Some(data) = content
+            .data
+            .as_deref()
+            .and_then(|d| self.redact_json_data(d, location, redactions))
+        {
+            content.set_data(data.into_boxed_slice());
+        }
+    }
+    // The redacted document, None if `data` is no JSON or had nothing to mask.
+    fn redact_json_data(
+        &self,
+        data: &[u8],
+        location: Location,
+        redactions: &mut Vec<Redaction>,
+    ) -> Option<Vec<u8>> {
+        if self.json_paths.is_empty() {
+            return None;
+        }
+        let mut value: serde_json::Value = serde_json::from_slice(data).ok()?;
+        let mut found = Vec::new();
+        for path in self.json_paths.iter() {
+            redact_json(&mut value, path.as_slice(), &mut Vec::new(), &mut found);
+        }
+        if found.is_empty() {
+            return None;
+        }
+        redactions.extend(found.into_iter().map(|name| Redaction { location, name }));
+        Some(serde_json::to_vec(&value).unwrap())
+    }
+    // Applies the JSON paths and masks the values found by the other rules in
+    // an event or message payload.
+    fn redact_payload(
+        &self,
+        data: &[u8],
+        location: Location,
+        secrets: &[Secret],
+        redactions: &mut Vec<Redaction>,
+    ) -> Option<Vec<u8>> {
+        let mut ret = self.redact_json_data(data, location, redactions);
+        for secret in secrets {
+            if let Some(d) = replace_all(
+                ret.as_deref().unwrap_or(data),
+                &secret.value,
+                REDACTED.as_bytes(),
+            ) {
+                ret = Some(d);
+                redactions.push(Redaction {
+                    location,
+                    name: secret.name.clone(),
+                });
+            }
+        }
+        ret
+    }
+    fn redact_events(
+        &self,
+        events: &mut [sse::Event],
+        content: Option<&mut Content>,
+        secrets: &[Secret],
+        redactions: &mut Vec<Redaction>,
+    ) {
+        let mut redacted = false;
+        for e in events.iter_mut() {
+            if let Some(data) =
+                self.redact_payload(e.data.as_bytes(), Location::Event, secrets, redactions)
+            {
+                e.data = String::from_utf8_lossy(&data).into_owned();
+                redacted = true;
+            }
+        }
+        // the stream still holds the original data
+        if let Some(c) = content.filter(|_| redacted) {
+            c.set_data(sse::encode(events).into_bytes().into_boxed_slice());
+        }
+    }
+    fn redact_websocket(
+        &self,
+        websocket: &mut WebSocket,
+        secrets: &[Secret],
+        redactions: &mut Vec<Redaction>,
+    ) {
+        for m in websocket.messages.iter_mut() {
+            if !matches!(m.opcode, Opcode::Text | Opcode::Binary) {
+                continue;
+            }
+            if let Some(data) =
+                self.redact_payload(&m.data, Location::WebSocketMessage, secrets, redactions)
+            {
+                m.data = data.into_boxed_slice();
+            }
+        }
+    }
+    /// Masks the configured values and records them in `entry.redactions`.
+    /// Values masked by the header, cookie and parameter rules are masked in
+    /// server-sent events and WebSocket messages as well.
+    pub fn redact(&self, entry: &mut Entry) {
+        if self.is_empty() {
+            return;
+        }
+        let mut redactions = Vec::new();
+        let mut secrets = Vec::new();
+        let request = &mut entry.request;
+
+        for c in request.cookies.0.iter_mut() {
+            if self.cookies.contains(&c.name) {
+                secrets.push(Secret {
+                    name: c.name.clone(),
+                    value: std::mem::replace(&mut c.value, REDACTED.to_string()).into_bytes(),
+                });
+                redactions.push(Redaction {
+                    location: Location::RequestCookie,
+                    name: c.name.clone(),
+                });
+            }
+        }
+        if !self.cookies.is_empty() {
+            self.redact_cookie_headers(&mut request.headers);
+        }
+        self.redact_headers(
+            &mut request.headers,
+            Location::RequestHeader,
+            &mut redactions,
+            &mut secrets,
+        );
+
+        let url = &mut request.url;
+        if url.query.iter().any(|q| self.is_param(q.name.as_str())) {
+            for q in url.query.iter_mut() {
+                if self.is_param(q.name.as_str()) {
+                    secrets.push(Secret {
+                        name: q.name.clone(),
+                        value: std::mem::replace(&mut q.value, REDACTED.to_string()).into_bytes(),
+                    });
+                    redactions.push(Redaction {
+                        location: Location::Query,
+                        name: q.name.clone(),
+                    });
+                }
+            }
+            // pairs that are kept stay as they were sent
+            let query = url
+                .url
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .map(|pair| {
+                    let (name, _) = pair.split_once('=').unwrap_or((pair, ""));
+                    let decoded = url::form_urlencoded::parse(name.as_bytes())
+                        .next()
+                        .map(|(n, _)| n);
+                    match decoded.filter(|n| self.is_param(n)) {
+                        Some(_) => format!(
+                            "{}={}",
+                            name,
+                            url::form_urlencoded::byte_serialize(REDACTED.as_bytes())
+                                .collect::<String>()
+                        ),
+                        None => pair.to_string(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            url.url.set_query(Some(&query));
+        }
+
+        match &mut request.body {
+            Some(request::Body::UrlEncodedForm(f)) => {
+                for e in f.iter_mut() {
+                    if self.is_param(e.name.as_str()) {
+                        secrets.push(Secret {
+                            name: e.name.clone(),
+                            value: std::mem::replace(&mut e.value, REDACTED.to_string())
+                                .into_bytes(),
+                        });
+                        redactions.push(Redaction {
+                            location: Location::Form,
+                            name: e.name.clone(),
+                        });
+                    }
+                }
+            }
+            Some(request::Body::MultipartForm(f)) => {
+                for e in f.iter_mut() {
+                    if let Some(name) = e.name.as_ref().filter(|n| self.is_param(n)) {
+                        if let Some(value) = e.content.data.as_deref() {
+                            secrets.push(Secret {
+                                name: name.clone(),
+                                value: value.to_vec(),
+                            });
+                        }
+                        e.content.set_data(REDACTED.as_bytes().into());
+                        redactions.push(Redaction {
+                            location: Location::Form,
+                            name: name.clone(),
+                        });
+                    }
+                }
+            }
+            Some(request::Body::Content(c)) => {
+                self.redact_json(c, Location::RequestJson, &mut redactions)
+            }
+            None => (),
+        }
+
+        if let Some(response) = &mut entry.response {
+            for c in response.cookies.0.iter_mut() {
+                if self.cookies.contains(&c.name) {
+                    secrets.push(Secret {
+                        name: c.name.clone(),
+                        value: std::mem::replace(&mut c.value, REDACTED.to_string()).into_bytes(),
+                    });
+                    redactions.push(Redaction {
+                        location: Location::ResponseCookie,
+                        name: c.name.clone(),
+                    });
+                }
+            }
+            if !self.cookies.is_empty() {
+                self.redact_cookie_headers(&mut response.headers);
+            }
+            self.redact_headers(
+                &mut response.headers,
+                Location::ResponseHeader,
+                &mut redactions,
+                &mut secrets,
+            );
+            if let Some(c) = &mut response.content {
+                self.redact_json(c, Location::ResponseJson, &mut redactions);
+            }
+        }
+
+        secrets.retain(|s| s.value.len() >= MIN_SECRET_LEN);
+        if let Some(response) = &mut entry.response {
+            if let Some(events) = &mut response.events {
+                self.redact_events(events, response.content.as_mut(), &secrets, &mut redactions);
+            }
+        }
+        if let Some(websocket) = &mut entry.websocket {
+            self.redact_websocket(websocket, &secrets, &mut redactions);
+        }
+        entry.redactions.extend(redactions);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    content::Content,
+    content::{Content, Encoding, Payload},
     header::{self, Headers, InvalidHeader},
     url::Url,
     HttpVersion, Method,
@@ -8,8 +8,8 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cookie {
-    name: String,
-    value: String,
+    pub name: String,
+    pub value: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,6 +54,13 @@ pub struct UrlEncodedFormEntry {
     pub name: String,
     pub value: String,
 }
+impl UrlEncodedFormEntry {
+    pub fn encode(entries: &[Self]) -> String {
+        url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(entries.iter().map(|e| (e.name.as_str(), e.value.as_str())))
+            .finish()
+    }
+}
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MultipartFormEntry {
     pub name: Option<String>,
@@ -75,6 +82,30 @@ pub enum MultipartFormParseError {
     },
 }
 impl MultipartFormEntry {
+    /// Rebuilds the form with the boundary of the Content-Type header, None
+    /// when the boundary is unknown or a field was not recorded.
+    pub fn encode(entries: &[Self], headers: &Headers) -> Option<Vec<u8>> {
+        let content_type = headers.content_type().ok()??;
+        let boundary = multer::parse_boundary(content_type).ok()?;
+        let mut body = Vec::new();
+        for e in entries {
+            body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            for h in &e.headers.0 {
+                body.extend_from_slice(h.name.as_str().as_bytes());
+                body.extend_from_slice(b": ");
+                body.extend_from_slice(h.value.as_bytes());
+                body.extend_from_slice(b"\r\n");
+            }
+            body.extend_from_slice(b"\r\n");
+            body.extend_from_slice(e.content.data.as_deref().or(match e.content.size {
+                0 => Some(&[]),
+                _ => None,
+            })?);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        Some(body)
+    }
     pub fn parse(
         url: &str,
         content_type: &str,
@@ -142,35 +173,48 @@ pub enum BodyParseError {
     ),
 }
 impl Body {
-    pub fn parse(url: &str, headers: &Headers, content: &[u8]) -> Result<Self, BodyParseError> {
+    /// Parses a body along with the Content-Encoding of a form, which is
+    /// parsed decoded. A form that fails to decode is kept as content.
+    pub fn parse(
+        url: &str,
+        headers: &Headers,
+        payload: Payload<'_>,
+    ) -> Result<(Self, Option<Encoding>), BodyParseError> {
         let content_type = headers
             .content_type()
             .map_err(|_| BodyParseError::BinaryHeader)?;
         if let Some(content_type_str) = content_type {
             let content_type: mime::Mime =
                 content_type_str.parse().map_err(BodyParseError::from)?;
-            if content_type == mime::APPLICATION_WWW_FORM_URLENCODED {
-                return Ok(Body::UrlEncodedForm(
-                    url::form_urlencoded::parse(content)
-                        .map(|(k, v)| UrlEncodedFormEntry {
-                            name: k.to_string(),
-                            value: v.to_string(),
-                        })
-                        .collect(),
-                ));
-            } else if content_type == mime::MULTIPART_FORM_DATA {
-                return Ok(Body::MultipartForm(MultipartFormEntry::parse(
-                    url,
-                    content_type_str,
-                    content,
-                )?));
+            if content_type == mime::APPLICATION_WWW_FORM_URLENCODED
+                || content_type == mime::MULTIPART_FORM_DATA
+            {
+                let (encoding, content) = payload.decode(headers);
+                if encoding.as_ref().is_none_or(|e| e.decoded) {
+                    let body = if content_type == mime::APPLICATION_WWW_FORM_URLENCODED {
+                        Body::UrlEncodedForm(
+                            url::form_urlencoded::parse(&content)
+                                .map(|(k, v)| UrlEncodedFormEntry {
+                                    name: k.to_string(),
+                                    value: v.to_string(),
+                                })
+                                .collect(),
+                        )
+                    } else {
+                        Body::MultipartForm(MultipartFormEntry::parse(
+                            url,
+                            content_type_str,
+                            &content,
+                        )?)
+                    };
+                    return Ok((body, encoding));
+                }
             }
         }
-        Ok(Self::Content(Content::from_mime(
-            url,
-            content_type,
-            content.to_owned().into_boxed_slice(),
-        )))
+        Ok((
+            Self::Content(Content::from_payload(url, content_type, headers, payload)),
+            None,
+        ))
     }
 }
 
@@ -182,6 +226,9 @@ pub struct Request {
     pub headers: Headers,
     pub cookies: Cookies,
     pub body: Option<Body>,
+    /// of a form body, the encoding of other bodies is kept in their content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub form_encoding: Option<Encoding>,
 }
 #[derive(Debug, thiserror::Error)]
 pub enum InvalidRequest {
@@ -223,21 +270,24 @@ impl Request {
         method: &str,
         url: &str,
         headers: I,
-        content: Option<&[u8]>,
+        content: Option<Payload<'_>>,
     ) -> Result<Self, InvalidRequest> {
         let headers = Headers::parse(headers).map_err(InvalidRequest::from)?;
+        let (body, form_encoding) = match content {
+            Some(content) if !content.is_empty() => {
+                let (body, encoding) =
+                    Body::parse(url, &headers, content).map_err(InvalidRequest::from)?;
+                (Some(body), encoding)
+            }
+            _ => (None, None),
+        };
         Ok(Self {
             http_version: http_version.parse().map_err(InvalidRequest::from)?,
             method: method.parse().unwrap(),
             url: url.parse().map_err(InvalidRequest::from)?,
             cookies: Cookies::parse(&headers).map_err(InvalidRequest::from)?,
-            body: match content {
-                Some([]) => None,
-                Some(content) => {
-                    Some(Body::parse(url, &headers, content).map_err(InvalidRequest::from)?)
-                }
-                None => None,
-            },
+            body,
+            form_encoding,
             headers,
         })
     }
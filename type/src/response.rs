@@ -1,7 +1,7 @@
 use crate::{
-    content::Content,
+    content::{Content, Payload},
     header::{self, HeaderValue, Headers, InvalidHeader},
-    serde_date_time, HttpVersion, StatusCode,
+    serde_date_time, sse, HttpVersion, StatusCode,
 };
 use serde::{Deserialize, Serialize};
 
@@ -94,6 +94,22 @@ impl Cookies {
     }
 }
 
+/// Locates a part of a streamed body, or of the messages of a WebSocket
+/// connection. Long-lived streams and connections are recorded in parts while
+/// they are open, each in an entry of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    /// index of the entry holding the first segment
+    pub first: u32,
+    /// of the segment in the body as transferred, so the segments of an
+    /// encoded body have to be joined before they can be decoded, or of its
+    /// first message among those of the connection
+    pub offset: u64,
+    /// set on the segment recorded once the response completed or the
+    /// connection closed
+    pub last: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Response {
     pub http_version: HttpVersion,
@@ -101,6 +117,12 @@ pub struct Response {
     pub headers: Headers,
     pub cookies: Cookies,
     pub content: Option<Content>,
+    /// parsed events of a `text/event-stream` body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<sse::Event>>,
+    /// set if the content is a segment of a streamed body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment: Option<Segment>,
 }
 #[derive(Debug, thiserror::Error)]
 pub enum InvalidResponse {
@@ -131,25 +153,33 @@ impl Response {
         status_code: u16,
         url: &str,
         headers: I,
-        content: Option<&[u8]>,
+        content: Option<Payload<'_>>,
     ) -> Result<Self, InvalidResponse> {
         let headers = Headers::parse(headers).map_err(InvalidResponse::from)?;
+        let content = match content {
+            Some(content) if content.is_empty() => None,
+            Some(content) => Some(Content::from_payload(
+                url,
+                headers
+                    .content_type()
+                    .map_err(|_| InvalidResponse::ContentType)?,
+                &headers,
+                content,
+            )),
+            None => None,
+        };
         Ok(Self {
             http_version: http_version.parse().map_err(InvalidResponse::from)?,
             status_code: StatusCode(status_code),
             cookies: Cookies::parse_headers(&headers).map_err(InvalidResponse::from)?,
-            content: match content {
-                Some([]) => None,
-                Some(content) => Some(Content::from_mime(
-                    url,
-                    headers
-                        .content_type()
-                        .map_err(|_| InvalidResponse::ContentType)?,
-                    content.to_vec().into_boxed_slice(),
-                )),
-                None => None,
-            },
+            events: content
+                .as_ref()
+                .filter(|c| c.content_type.essence_str() == sse::CONTENT_TYPE)
+                .and_then(|c| c.data.as_deref())
+                .map(|d| sse::parse(String::from_utf8_lossy(d).as_ref())),
+            content,
             headers,
+            segment: None,
         })
     }
 }
@@ -0,0 +1,470 @@
+//! Command lines and code that send a recorded request again.
+//!
+//! The framing headers are left to the tools, which compute them for the
+//! body they send. Cookies are passed as recorded, in their encoded form.
+//! Multipart file fields refer to a file of the recorded name in the working
+//! directory instead of inlining its content.
+
+use crate::{
+    content::Content,
+    header::{self, HeaderValue},
+    request::{Body, MultipartFormEntry, Request, UrlEncodedFormEntry},
+    HttpVersion, Method,
+};
+use std::{fmt::Display, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Curl,
+    Httpie,
+    Reqwest,
+}
+impl Language {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Curl => "curl",
+            Self::Httpie => "httpie",
+            Self::Reqwest => "reqwest",
+        }
+    }
+}
+#[derive(Debug, thiserror::Error)]
+#[error("unknown snippet language {0:?}, expected curl, httpie or reqwest")]
+pub struct InvalidLanguage(String);
+impl FromStr for Language {
+    type Err = InvalidLanguage;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "curl" => Ok(Self::Curl),
+            "httpie" => Ok(Self::Httpie),
+            "reqwest" => Ok(Self::Reqwest),
+            s => Err(InvalidLanguage(s.to_string())),
+        }
+    }
+}
+impl Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+enum Data<'a> {
+    None,
+    Raw(&'a [u8]),
+    Form(&'a [UrlEncodedFormEntry]),
+    Multipart(&'a [MultipartFormEntry]),
+}
+
+// The parts of a request a snippet is made of.
+struct Parts<'a> {
+    method: &'a Method,
+    url: &'a str,
+    headers: Vec<(&'a str, &'a [u8])>,
+    cookie: Option<String>,
+    data: Data<'a>,
+    // the body or a form field was not recorded
+    missing: bool,
+}
+impl<'a> Parts<'a> {
+    fn new(request: &'a Request) -> Self {
+        let url = &request.url.url;
+        let authority = match url.port() {
+            Some(p) => format!("{}:{}", url.host_str().unwrap_or_default(), p),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        // the body is sent as recorded, decoded unless that failed
+        let decoded = match &request.body {
+            Some(Body::Content(c)) => c.encoding.as_ref().is_none_or(|e| e.decoded),
+            _ => true,
+        };
+        let form = matches!(
+            request.body,
+            Some(Body::UrlEncodedForm(_) | Body::MultipartForm(_))
+        );
+        let headers = request
+            .headers
+            .0
+            .iter()
+            .filter(|h| {
+                !(h.name == header::CONTENT_LENGTH
+                    || h.name == header::TRANSFER_ENCODING
+                    || h.name == header::CONNECTION
+                    || h.name == header::KEEP_ALIVE
+                    || h.name == header::TE
+                    || h.name == header::COOKIE
+                    || (h.name == header::HOST
+                        && h.value.as_bytes().eq_ignore_ascii_case(authority.as_bytes()))
+                    || (h.name == header::CONTENT_ENCODING && decoded)
+                    // the tools set it with their own boundary
+                    || (h.name == header::CONTENT_TYPE && form))
+            })
+            .map(|h| (h.name.as_str(), h.value.as_bytes()))
+            .collect();
+        let cookies: Vec<_> = request
+            .headers
+            .0
+            .iter()
+            .filter(|h| h.name == header::COOKIE)
+            .map(|h| h.value.to_string_lossy())
+            .collect();
+        let (data, missing) = match &request.body {
+            None => (Data::None, false),
+            Some(Body::Content(c)) => match c.data.as_deref() {
+                Some([]) => (Data::None, false),
+                Some(d) => (Data::Raw(d), false),
+                None => (Data::None, c.size != 0),
+            },
+            Some(Body::UrlEncodedForm(f)) => (Data::Form(f), false),
+            Some(Body::MultipartForm(f)) => (
+                Data::Multipart(f),
+                f.iter()
+                    .any(|f| f.file_name.is_none() && f.content.data.is_none()),
+            ),
+        };
+        Self {
+            method: &request.method,
+            url: url.as_str(),
+            headers,
+            cookie: Some(cookies.join("; ")).filter(|c| !c.is_empty()),
+            data,
+            missing,
+        }
+    }
+}
+
+fn field_data(content: &Content) -> &[u8] {
+    content.data.as_deref().unwrap_or_default()
+}
+fn field_type(field: &MultipartFormEntry) -> Option<&HeaderValue> {
+    field.headers.get(&header::CONTENT_TYPE)
+}
+
+// Unquoted when only made of characters without meaning to the shell, ANSI-C
+// quoted when it has to hold control characters or invalid UTF-8.
+fn shell_quote(value: &[u8]) -> String {
+    let plain = |b: &u8| b.is_ascii_alphanumeric() || b"_@%+=:,./-".contains(b);
+    if !value.is_empty() && value.iter().all(plain) {
+        return String::from_utf8_lossy(value).into_owned();
+    }
+    match std::str::from_utf8(value) {
+        Ok(s) if !s.chars().any(|c| c.is_control() && c != '\n' && c != '\t') => {
+            format!("'{}'", s.replace('\'', r"'\''"))
+        }
+        _ => {
+            let mut ret = String::from("$'");
+            for b in value {
+                match b {
+                    b'\\' | b'\'' => {
+                        ret.push('\\');
+                        ret.push(char::from(*b));
+                    }
+                    b' '..=b'~' => ret.push(char::from(*b)),
+                    _ => ret.push_str(&format!("\\x{:02x}", b)),
+                }
+            }
+            ret.push('\'');
+            ret
+        }
+    }
+}
+fn shell_lines(comment: bool, lines: Vec<String>) -> String {
+    let mut ret = String::new();
+    if comment {
+        ret.push_str("# the request body was not recorded completely\n");
+    }
+    ret.push_str(&lines.join(" \\\n  "));
+    ret.push('\n');
+    ret
+}
+// `Name;` sends an empty header to both tools, `Name:` would remove it.
+fn header_item(name: &str, separator: &[u8], value: &[u8]) -> Vec<u8> {
+    match value.is_empty() {
+        true => [name.as_bytes(), b";"].concat(),
+        false => [name.as_bytes(), separator, value].concat(),
+    }
+}
+// httpie splits an item at the first separator character not escaped with a
+// backslash, so those are escaped in names and at the start of values.
+fn httpie_name(name: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(name.len());
+    for &b in name {
+        if matches!(b, b'=' | b'@' | b':' | b';') {
+            escaped.push(b'\\');
+        }
+        escaped.push(b);
+    }
+    escaped
+}
+
+fn httpie_item(name: &[u8], separator: &[u8], value: &[u8]) -> String {
+    let escape: &[u8] = match value.first() {
+        Some(b'=' | b'@' | b':') => b"\\",
+        _ => b"",
+    };
+    shell_quote(&[&httpie_name(name), separator, escape, value].concat())
+}
+
+fn curl(request: &Request, parts: &Parts<'_>) -> String {
+    let mut first = String::from("curl");
+    match request.http_version {
+        HttpVersion::Http10 => first.push_str(" --http1.0"),
+        HttpVersion::H2 => first.push_str(" --http2"),
+        HttpVersion::H3 => first.push_str(" --http3"),
+        HttpVersion::Http09 | HttpVersion::Http11 => (),
+    }
+    // curl picks GET, or POST when sending data
+    let default = match parts.data {
+        Data::None => Method::Get,
+        _ => Method::Post,
+    };
+    match parts.method {
+        Method::Head => first.push_str(" --head"),
+        m if *m == default => (),
+        m => first.push_str(&format!(" -X {}", shell_quote(m.as_str().as_bytes()))),
+    }
+    first.push(' ');
+    first.push_str(&shell_quote(parts.url.as_bytes()));
+    let mut lines = vec![first];
+    for (name, value) in &parts.headers {
+        lines.push(format!(
+            "-H {}",
+            shell_quote(&header_item(name, b": ", value))
+        ));
+    }
+    if parts
+        .headers
+        .iter()
+        .any(|(n, _)| n.eq_ignore_ascii_case("accept-encoding"))
+    {
+        lines.push(String::from("--compressed"));
+    }
+    match &parts.cookie {
+        // -b reads a cookie file for a value without `=`
+        Some(c) if !c.contains('=') => lines.push(format!(
+            "-H {}",
+            shell_quote(&header_item("Cookie", b": ", c.as_bytes()))
+        )),
+        Some(c) => lines.push(format!("-b {}", shell_quote(c.as_bytes()))),
+        None => (),
+    }
+    match parts.data {
+        Data::None => (),
+        Data::Raw(d) => lines.push(format!("--data-raw {}", shell_quote(d))),
+        Data::Form(f) => {
+            for e in f {
+                // curl only encodes the value, and reads a file for a name
+                // holding `@`
+                let name: String =
+                    url::form_urlencoded::byte_serialize(e.name.as_bytes()).collect();
+                lines.push(match name.is_empty() {
+                    // `=value` would be sent without its `=`
+                    true => format!(
+                        "--data-raw {}",
+                        shell_quote(
+                            UrlEncodedFormEntry::encode(std::slice::from_ref(e)).as_bytes()
+                        )
+                    ),
+                    false => format!(
+                        "--data-urlencode {}",
+                        shell_quote(format!("{}={}", name, e.value).as_bytes())
+                    ),
+                });
+            }
+        }
+        Data::Multipart(f) => {
+            for e in f {
+                let name = e.name.as_deref().unwrap_or_default();
+                let line = match &e.file_name {
+                    Some(file_name) => {
+                        let mut v = format!("{}=@{}", name, file_name);
+                        if let Some(t) = field_type(e) {
+                            v.push_str(";type=");
+                            v.push_str(&t.to_string_lossy());
+                        }
+                        format!("-F {}", shell_quote(v.as_bytes()))
+                    }
+                    // -F reads files for `@` and `<` and parses `;type=` and
+                    // the like out of the value
+                    None => format!(
+                        "--form-string {}",
+                        shell_quote(&[name.as_bytes(), b"=", field_data(&e.content)].concat())
+                    ),
+                };
+                lines.push(line);
+            }
+        }
+    }
+    shell_lines(parts.missing, lines)
+}
+
+fn httpie(parts: &Parts<'_>) -> String {
+    let mut first = String::from("http");
+    match parts.data {
+        Data::Form(_) => first.push_str(" --form"),
+        Data::Multipart(_) => first.push_str(" --multipart"),
+        Data::None | Data::Raw(_) => (),
+    }
+    first.push_str(&format!(
+        " {} {}",
+        shell_quote(parts.method.as_str().as_bytes()),
+        shell_quote(parts.url.as_bytes())
+    ));
+    let mut lines = vec![first];
+    if let Data::Raw(d) = parts.data {
+        lines.push(format!("--raw {}", shell_quote(d)));
+    }
+    for (name, value) in &parts.headers {
+        lines.push(match value.is_empty() {
+            true => shell_quote(&header_item(name, b":", value)),
+            false => httpie_item(name.as_bytes(), b":", value),
+        });
+    }
+    if let Some(c) = &parts.cookie {
+        lines.push(httpie_item(b"Cookie", b":", c.as_bytes()));
+    }
+    match parts.data {
+        Data::None | Data::Raw(_) => (),
+        Data::Form(f) => {
+            for e in f {
+                lines.push(httpie_item(e.name.as_bytes(), b"=", e.value.as_bytes()));
+            }
+        }
+        Data::Multipart(f) => {
+            for e in f {
+                let name = e.name.as_deref().unwrap_or_default();
+                let item = match &e.file_name {
+                    Some(file_name) => {
+                        let mut v = httpie_name(name.as_bytes());
+                        v.push(b'@');
+                        v.extend_from_slice(file_name.as_bytes());
+                        if let Some(t) = field_type(e) {
+                            v.extend_from_slice(b";type=");
+                            v.extend_from_slice(t.as_bytes());
+                        }
+                        shell_quote(&v)
+                    }
+                    None => httpie_item(name.as_bytes(), b"=", field_data(&e.content)),
+                };
+                lines.push(item);
+            }
+        }
+    }
+    shell_lines(parts.missing, lines)
+}
+
+// A `&str` literal for UTF-8, a `&[u8]` one otherwise.
+fn rust_literal(value: &[u8]) -> String {
+    match std::str::from_utf8(value) {
+        Ok(s) => format!("{:?}", s),
+        Err(_) => format!("&b\"{}\"[..]", value.escape_ascii()),
+    }
+}
+
+fn reqwest(parts: &Parts<'_>) -> String {
+    let mut ret = String::new();
+    if parts.missing {
+        ret.push_str("// the request body was not recorded completely\n");
+    }
+    let method = match parts.method {
+        Method::Extension(m) => format!("reqwest::Method::from_bytes(b{:?})?", m),
+        m => format!("reqwest::Method::{}", m.as_str()),
+    };
+    ret.push_str("let response = reqwest::blocking::Client::new()\n");
+    ret.push_str(&format!(
+        "    .request({}, {})\n",
+        method,
+        rust_literal(parts.url.as_bytes())
+    ));
+    for (name, value) in &parts.headers {
+        ret.push_str(&format!(
+            "    .header({}, {})\n",
+            rust_literal(name.as_bytes()),
+            rust_literal(value)
+        ));
+    }
+    if let Some(c) = &parts.cookie {
+        ret.push_str(&format!(
+            "    .header(\"Cookie\", {})\n",
+            rust_literal(c.as_bytes())
+        ));
+    }
+    match parts.data {
+        Data::None => (),
+        Data::Raw(d) => ret.push_str(&format!("    .body({})\n", rust_literal(d))),
+        Data::Form(f) => {
+            ret.push_str("    .form(&[\n");
+            for e in f {
+                ret.push_str(&format!("        ({:?}, {:?}),\n", e.name, e.value));
+            }
+            ret.push_str("    ])\n");
+        }
+        Data::Multipart(f) => {
+            ret.push_str("    .multipart(\n");
+            ret.push_str("        reqwest::blocking::multipart::Form::new()");
+            for e in f {
+                let name = format!("{:?}", e.name.as_deref().unwrap_or_default());
+                let data = field_data(&e.content);
+                ret.push_str(&match (&e.file_name, std::str::from_utf8(data)) {
+                    (Some(file_name), _) => {
+                        format!("\n            .file({}, {:?})?", name, file_name)
+                    }
+                    (None, Ok(s)) => format!("\n            .text({}, {:?})", name, s),
+                    (None, Err(_)) => format!(
+                        "\n            .part({}, reqwest::blocking::multipart::Part::bytes({}))",
+                        name,
+                        rust_literal(data)
+                    ),
+                });
+            }
+            ret.push_str(",\n    )\n");
+        }
+    }
+    ret.push_str("    .send()?;\n");
+    ret
+}
+
+/// A snippet in `language` that sends `request` again.
+pub fn generate(request: &Request, language: Language) -> String {
+    let parts = Parts::new(request);
+    match language {
+        Language::Curl => curl(request, &parts),
+        Language::Httpie => httpie(&parts),
+        Language::Reqwest => reqwest(&parts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote() {
+        assert_eq!(shell_quote(b"a-b_c/d.e:f@g=1,2%3+"), "a-b_c/d.e:f@g=1,2%3+");
+        assert_eq!(shell_quote(b""), "''");
+        assert_eq!(shell_quote(b"a b"), "'a b'");
+        assert_eq!(shell_quote(b"it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(b"''"), r"''\'''\'''");
+        assert_eq!(shell_quote(b"$HOME `id` \\"), r"'$HOME `id` \'");
+        assert_eq!(shell_quote("line\n\ttab é".as_bytes()), "'line\n\ttab é'");
+        // control bytes and invalid UTF-8 are only safe in ANSI-C quotes
+        assert_eq!(shell_quote(b"a\rb\x1b'\\"), r"$'a\x0db\x1b\'\\'");
+        assert_eq!(shell_quote(b"\xff\xfe it's"), r"$'\xff\xfe it\'s'");
+        assert_eq!(shell_quote(b"\x00"), r"$'\x00'");
+    }
+
+    #[test]
+    fn empty_values() {
+        assert_eq!(header_item("X-Empty", b": ", b""), b"X-Empty;");
+        assert_eq!(header_item("X-Key", b": ", b"v"), b"X-Key: v");
+        assert_eq!(httpie_item(b"name", b"=", b""), "name=");
+        assert_eq!(httpie_item(b"name", b"=", b"@file"), r"'name=\@file'");
+        assert_eq!(httpie_item(b"name", b"=", b":x"), r"'name=\:x'");
+        assert_eq!(httpie_item(b"a:b=c@", b"=", b"1"), r"'a\:b\=c\@=1'");
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(rust_literal(b"say \"hi\"\n"), r#""say \"hi\"\n""#);
+        assert_eq!(rust_literal(b"\xff\x00a"), r#"&b"\xff\x00a"[..]"#);
+    }
+}
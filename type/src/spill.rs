@@ -0,0 +1,218 @@
+//! Layout of the temporary spill directory written by the mitmproxy saver
+//! and recovery of recordings from directories left behind by a crash.
+//!
+//! A spill directory holds every entry as `unpacked/<index>.bin` (CBOR) until
+//! it has been packed into `<n>.bin.xz`, a CBOR `Entries<Vec<Entry>>`
+//! compressed with the codec named by the extension.
+
+use crate::{
+    archive::{
+        codec::{self, Codec},
+        writer::{WriteError, WriteOptions},
+        Info, RecordingWriter,
+    },
+    Entries, Entry,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+pub const DIR_PREFIX: &str = "http-recorder-mitmproxy";
+pub const UNPACKED_DIR: &str = "unpacked";
+
+pub fn packed_file_name(pack: usize, codec: Codec) -> String {
+    format!("{}.bin{}", pack, codec.extension())
+}
+pub fn unpacked_file_name(index: u32) -> String {
+    format!("{}.bin", index)
+}
+
+/// Lists the spill directories left in `tmp_dir`, oldest first.
+pub fn find_dirs<P: AsRef<Path>>(tmp_dir: P) -> io::Result<Vec<PathBuf>> {
+    let mut ret = Vec::new();
+    for d in fs::read_dir(tmp_dir)? {
+        let d = d?;
+        if d.file_type()?.is_dir() && d.file_name().to_string_lossy().starts_with(DIR_PREFIX) {
+            ret.push((d.metadata()?.modified()?, d.path()));
+        }
+    }
+    ret.sort();
+    Ok(ret.into_iter().map(|(_, p)| p).collect())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpillFileError {
+    #[error("failed to read file")]
+    Io(
+        #[source]
+        #[from]
+        io::Error,
+    ),
+    #[error("failed to decode file")]
+    Decode(
+        #[source]
+        #[from]
+        ciborium::de::Error<io::Error>,
+    ),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecoverError {
+    #[error("failed to list {}", .path.display())]
+    ReadDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to write recording")]
+    Write(
+        #[source]
+        #[from]
+        WriteError,
+    ),
+}
+
+pub struct Recovered {
+    /// `None` if no entry could be recovered
+    pub info: Option<Info>,
+    /// files that could not be read, usually the ones being written when
+    /// the process died
+    pub skipped: Vec<(PathBuf, SpillFileError)>,
+}
+
+fn numbered_files(dir: &Path, suffix: &str) -> Result<BTreeMap<u32, PathBuf>, RecoverError> {
+    let read_dir_error = |e| RecoverError::ReadDir {
+        path: dir.to_path_buf(),
+        source: e,
+    };
+    let mut ret = BTreeMap::new();
+    let dir_entries = match fs::read_dir(dir) {
+        Ok(d) => d,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ret),
+        Err(e) => return Err(read_dir_error(e)),
+    };
+    for d in dir_entries {
+        let path = d.map_err(read_dir_error)?.path();
+        let n = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(suffix))
+            .and_then(|n| n.parse().ok());
+        if let Some(n) = n {
+            ret.insert(n, path);
+        }
+    }
+    Ok(ret)
+}
+
+fn read_unpacked(path: &Path) -> Result<Entry, SpillFileError> {
+    let data = fs::read(path).map_err(SpillFileError::from)?;
+    ciborium::de::from_reader(data.as_slice()).map_err(SpillFileError::from)
+}
+fn read_packed(path: &Path, codec: Codec) -> Result<Entries<Vec<Entry>>, SpillFileError> {
+    let mut data = Vec::new();
+    codec::decoder(
+        io::BufReader::new(fs::File::open(path).map_err(SpillFileError::from)?),
+        codec,
+    )
+    .map_err(SpillFileError::from)?
+    .read_to_end(&mut data)
+    .map_err(SpillFileError::from)?;
+    ciborium::de::from_reader(data.as_slice()).map_err(SpillFileError::from)
+}
+
+struct Rebuild<'a> {
+    dest: &'a Path,
+    options: WriteOptions,
+    writer: Option<RecordingWriter>,
+    last: Option<u32>,
+    skipped: Vec<(PathBuf, SpillFileError)>,
+}
+impl Rebuild<'_> {
+    fn add(&mut self, entry: Entry) -> Result<(), RecoverError> {
+        // packs and loose files overlap if the process died between packing
+        // and removing the loose files
+        if self.last.is_some_and(|l| entry.index <= l) {
+            return Ok(());
+        }
+        let w = match &mut self.writer {
+            Some(w) => w,
+            None => self.writer.insert(
+                RecordingWriter::with_options(self.dest, &entry, self.options.clone())
+                    .map_err(RecoverError::from)?,
+            ),
+        };
+        w.add_entry(&entry).map_err(RecoverError::from)?;
+        self.last = Some(entry.index);
+        Ok(())
+    }
+    fn add_unpacked(&mut self, path: PathBuf) -> Result<(), RecoverError> {
+        match read_unpacked(path.as_path()) {
+            Ok(e) => self.add(e),
+            Err(e) => {
+                self.skipped.push((path, e));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Rebuilds a recording at `dest` from the spill directory `spill_dir`.
+///
+/// Entries are taken from the packed files first and completed with the
+/// loose ones; unreadable files are skipped and reported.
+pub fn recover<P: AsRef<Path>, Q: AsRef<Path>>(
+    spill_dir: P,
+    dest: Q,
+    options: WriteOptions,
+) -> Result<Recovered, RecoverError> {
+    let spill_dir = spill_dir.as_ref();
+    let mut unpacked = numbered_files(spill_dir.join(UNPACKED_DIR).as_path(), ".bin")?;
+    let mut packed = BTreeMap::new();
+    for c in [Codec::Xz, Codec::Zstd, Codec::None] {
+        let suffix = format!(".bin{}", c.extension());
+        for (n, path) in numbered_files(spill_dir, suffix.as_str())? {
+            packed.insert(n, (path, c));
+        }
+    }
+    let mut rebuild = Rebuild {
+        dest: dest.as_ref(),
+        options,
+        writer: None,
+        last: None,
+        skipped: Vec::new(),
+    };
+    for (path, codec) in packed.into_values() {
+        let entries = match read_packed(path.as_path(), codec) {
+            Ok(e) => e,
+            Err(e) => {
+                rebuild.skipped.push((path, e));
+                continue;
+            }
+        };
+        for entry in entries.data {
+            while let Some(u) = unpacked.first_entry() {
+                if *u.key() >= entry.index {
+                    break;
+                }
+                rebuild.add_unpacked(u.remove())?;
+            }
+            unpacked.remove(&entry.index);
+            rebuild.add(entry)?;
+        }
+    }
+    for path in unpacked.into_values() {
+        rebuild.add_unpacked(path)?;
+    }
+    Ok(Recovered {
+        info: rebuild
+            .writer
+            .map(RecordingWriter::finish)
+            .transpose()
+            .map_err(RecoverError::from)?,
+        skipped: rebuild.skipped,
+    })
+}
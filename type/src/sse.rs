@@ -0,0 +1,152 @@
+//! Parsing of `text/event-stream` bodies as specified by the HTML living
+//! standard.
+
+use serde::{Deserialize, Serialize};
+
+pub const CONTENT_TYPE: &str = "text/event-stream";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// last event id as seen by the client, ids carry over to later events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// event type, `None` for the default `message`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    pub data: String,
+    /// reconnection time in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<u64>,
+}
+
+// Splits at CRLF, CR or LF. A trailing line without terminator is left
+// out, it belongs to an event that was cut off.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let end = rest.find(['\r', '\n'])?;
+        let line = &rest[..end];
+        rest = &rest[end..];
+        rest = rest
+            .strip_prefix("\r\n")
+            .or_else(|| rest.strip_prefix('\r'))
+            .or_else(|| rest.strip_prefix('\n'))
+            .unwrap_or(rest);
+        Some(line)
+    })
+}
+
+/// Parses the complete events of an event stream, an event that was not
+/// terminated by a blank line is dropped like a browser would.
+pub fn parse(text: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut id = None;
+    let mut event = None;
+    let mut retry = None;
+    let mut data = String::new();
+    for line in lines(text.strip_prefix('\u{feff}').unwrap_or(text)) {
+        if line.is_empty() {
+            if !data.is_empty() {
+                data.pop();
+                events.push(Event {
+                    id: id.clone(),
+                    event: event.take(),
+                    data: std::mem::take(&mut data),
+                    retry: retry.take(),
+                });
+            }
+            event = None;
+            retry = None;
+            continue;
+        }
+        if line.starts_with(':') {
+            continue;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => event = Some(value.to_string()),
+            "data" => {
+                data.push_str(value);
+                data.push('\n');
+            }
+            "id" if !value.contains('\0') => id = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                retry = value.parse().ok()
+            }
+            _ => (),
+        }
+    }
+    events
+}
+
+/// Length of the start of `data` up to the blank line ending its last
+/// complete event, `None` if no event is complete yet. Cutting a stream there
+/// keeps every event in one piece.
+pub fn complete_len(data: &[u8]) -> Option<usize> {
+    let mut ret = None;
+    let mut line_start = 0;
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'\r' | b'\n' => {
+                // a CR ending `data` may be followed by an LF in the next
+                // part, an empty line that dispatches nothing
+                let end = match &data[i..] {
+                    [b'\r', b'\n', ..] => i + 2,
+                    _ => i + 1,
+                };
+                if i == line_start {
+                    ret = Some(end);
+                }
+                i = end;
+                line_start = end;
+            }
+            _ => i += 1,
+        }
+    }
+    ret
+}
+
+/// Writes events as a stream that parses back to the same events. Comments,
+/// line endings and an unterminated last event of the original are lost.
+pub fn encode(events: &[Event]) -> String {
+    let mut ret = String::new();
+    let mut id = None;
+    for e in events {
+        if e.id != id {
+            if let Some(i) = &e.id {
+                ret.push_str(&format!("id: {}\n", i));
+            }
+            id = e.id.clone();
+        }
+        if let Some(event) = &e.event {
+            ret.push_str(&format!("event: {}\n", event));
+        }
+        if let Some(retry) = e.retry {
+            ret.push_str(&format!("retry: {}\n", retry));
+        }
+        for line in e.data.split('\n') {
+            ret.push_str(&format!("data: {}\n", line));
+        }
+        ret.push('\n');
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_events() {
+        assert_eq!(complete_len(b"data: a"), None);
+        assert_eq!(complete_len(b"data: a\n"), None);
+        assert_eq!(complete_len(b"data: a\n\ndata: b\n"), Some(9));
+        assert_eq!(complete_len(b"data: a\r\n\r\ndata: b\r\n"), Some(11));
+        assert_eq!(complete_len(b"data: a\r\r"), Some(9));
+        assert_eq!(complete_len(b"data: a\r\n\r\ndata: b\r\n\r\n"), Some(22));
+    }
+}
@@ -0,0 +1,45 @@
+//! TLS parameters negotiated on the client and server connections of an
+//! entry.
+
+use crate::content::SHA256Digest;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Certificate {
+    /// relative distinguished names, e.g. `("CN", "example.com")`
+    pub subject: Vec<(String, String)>,
+    pub issuer: Vec<(String, String)>,
+    /// DNS names and IP addresses of the subject alternative name extension
+    pub alt_names: Vec<String>,
+    #[serde(with = "crate::serde_date_time")]
+    pub not_before: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "crate::serde_date_time")]
+    pub not_after: chrono::DateTime<chrono::Utc>,
+    /// hex encoded
+    pub serial: String,
+    /// SHA-256 of the DER encoding
+    pub fingerprint: SHA256Digest,
+}
+impl Certificate {
+    /// The common name of the subject, if any.
+    pub fn common_name(&self) -> Option<&str> {
+        self.subject
+            .iter()
+            .find(|(k, _)| k == "CN")
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tls {
+    /// e.g. `TLSv1.3`
+    pub version: Option<String>,
+    /// OpenSSL name of the cipher suite, e.g. `TLS_AES_256_GCM_SHA384`
+    pub cipher: Option<String>,
+    /// negotiated application protocol, e.g. `h2`
+    pub alpn: Option<String>,
+    pub sni: Option<String>,
+    /// certificate chain presented by the peer, leaf first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub certificates: Vec<Certificate>,
+}
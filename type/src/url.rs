@@ -6,6 +6,14 @@ pub enum Host {
     Domain(String),
     Addr(std::net::IpAddr),
 }
+impl std::fmt::Display for Host {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Domain(d) => f.write_str(d),
+            Self::Addr(a) => a.fmt(f),
+        }
+    }
+}
 mod serde_url {
     use serde::{de, Deserializer, Serializer};
     use url::Url;
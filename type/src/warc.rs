@@ -0,0 +1,804 @@
+//! WARC (ISO 28500) export and import of entries.
+//!
+//! Each entry becomes a `request` and a `response` record holding the
+//! messages in HTTP/1.1 wire format. Bodies are written as recorded, that is
+//! with their Content-Encoding removed where it could be, so that the
+//! `WARC-Payload-Digest` is the SHA-256 kept in [`Content`]. Bodies that were
+//! not recorded are left out and their record marked `WARC-Truncated`.
+//! Timings beyond the start time, WebSocket messages and the client address
+//! are not part of the records.
+
+use crate::{
+    archive::{
+        self,
+        writer::{write_recording, WriteError, WriteOptions},
+    },
+    content::{Content, Digest, Payload, SHA256Digest},
+    header::{self, Headers},
+    request, response, tls, HttpVersion,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    io::{self, BufRead, Read, Write},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+};
+
+pub const WARC_VERSION: &str = "WARC/1.1";
+const HTTP_REQUEST: &str = "application/http;msgtype=request";
+const HTTP_RESPONSE: &str = "application/http;msgtype=response";
+
+fn base32(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut ret = String::new();
+    for chunk in data.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = buf.iter().fold(0u64, |acc, b| acc << 8 | u64::from(*b));
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..8 {
+            ret.push(match i < chars {
+                true => char::from(ALPHABET[(bits >> (35 - i * 5)) as usize & 0x1f]),
+                false => '=',
+            });
+        }
+    }
+    ret
+}
+fn digest_field(digest: &SHA256Digest) -> String {
+    format!("sha256:{}", base32(&digest.0))
+}
+fn sha256(data: &[u8]) -> SHA256Digest {
+    match Digest::sha256(data) {
+        Digest::SHA256(d) => d,
+    }
+}
+fn record_id(id: &str) -> String {
+    format!("<urn:uuid:{}>", id)
+}
+fn warc_date(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+struct Record {
+    fields: Vec<(&'static str, String)>,
+    block: Vec<u8>,
+}
+impl Record {
+    fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(WARC_VERSION.as_bytes())?;
+        writer.write_all(b"\r\n")?;
+        for (name, value) in &self.fields {
+            write!(writer, "{}: {}\r\n", name, value)?;
+        }
+        write!(
+            writer,
+            "WARC-Block-Digest: {}\r\nContent-Length: {}\r\n\r\n",
+            digest_field(&sha256(&self.block)),
+            self.block.len()
+        )?;
+        writer.write_all(&self.block)?;
+        writer.write_all(b"\r\n\r\n")
+    }
+}
+
+// A body as it is written to the record.
+struct Body<'a> {
+    data: Cow<'a, [u8]>,
+    // false when decoding failed and the Content-Encoding still applies
+    decoded: bool,
+    digest: SHA256Digest,
+}
+impl<'a> Body<'a> {
+    /// `None` for a body that was not recorded
+    fn content(content: &'a Content) -> Option<Self> {
+        Some(Self {
+            data: match &content.data {
+                Some(d) => Cow::Borrowed(d),
+                None if content.size == 0 => Cow::Borrowed(&[]),
+                None => return None,
+            },
+            decoded: content.encoding.as_ref().is_none_or(|e| e.decoded),
+            digest: match &content.digest {
+                Digest::SHA256(d) => d.clone(),
+            },
+        })
+    }
+    fn encoded(data: Vec<u8>) -> Self {
+        Self {
+            digest: sha256(&data),
+            data: Cow::Owned(data),
+            decoded: true,
+        }
+    }
+    fn request(request: &'a request::Request) -> Option<Self> {
+        match request.body.as_ref()? {
+            request::Body::Content(c) => Self::content(c),
+            request::Body::UrlEncodedForm(f) => Some(Self::encoded(
+                request::UrlEncodedFormEntry::encode(f).into_bytes(),
+            )),
+            request::Body::MultipartForm(f) => {
+                request::MultipartFormEntry::encode(f, &request.headers).map(Self::encoded)
+            }
+        }
+    }
+}
+
+// The framing headers are rewritten to match the body of the record, which
+// never has a Transfer-Encoding applied. Messages without data, e.g. HEAD and
+// 304 responses or bodies that were not recorded, keep the Content-Length and
+// Content-Encoding as recorded.
+fn http_message(start_line: String, headers: &Headers, body: Option<&Body<'_>>) -> Vec<u8> {
+    let body = body.filter(|b| !b.data.is_empty());
+    let mut message = start_line.into_bytes();
+    message.extend_from_slice(b"\r\n");
+    for h in &headers.0 {
+        if h.name == header::TRANSFER_ENCODING
+            || (h.name == header::CONTENT_LENGTH && body.is_some())
+            || (h.name == header::CONTENT_ENCODING && body.is_some_and(|b| b.decoded))
+        {
+            continue;
+        }
+        message.extend_from_slice(h.name.as_str().as_bytes());
+        message.extend_from_slice(b": ");
+        message.extend_from_slice(h.value.as_bytes());
+        message.extend_from_slice(b"\r\n");
+    }
+    if let Some(b) = body {
+        message.extend_from_slice(format!("Content-Length: {}\r\n", b.data.len()).as_bytes());
+    }
+    message.extend_from_slice(b"\r\n");
+    if let Some(b) = body {
+        message.extend_from_slice(&b.data);
+    }
+    message
+}
+
+// HTTP/2 and HTTP/3 messages are written as HTTP/1.1, the original version
+// is kept in `WARC-Protocol`.
+fn wire_version(version: HttpVersion) -> &'static str {
+    match version {
+        HttpVersion::Http10 => HttpVersion::Http10.as_str(),
+        _ => HttpVersion::Http11.as_str(),
+    }
+}
+fn protocol(version: HttpVersion) -> &'static str {
+    match version {
+        HttpVersion::Http09 => "http/0.9",
+        HttpVersion::Http10 => "http/1.0",
+        HttpVersion::Http11 => "http/1.1",
+        HttpVersion::H2 => "h2",
+        HttpVersion::H3 => "h3",
+    }
+}
+
+fn common_fields(entry: &crate::Entry, http_version: HttpVersion) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("WARC-Date", warc_date(entry.timings.start_time)),
+        ("WARC-Target-URI", entry.request.url.url.to_string()),
+    ];
+    if let Some(addr) = entry.server_addr {
+        fields.push(("WARC-IP-Address", addr.ip().to_string()));
+    }
+    fields.push(("WARC-Protocol", protocol(http_version).to_string()));
+    if let Some(tls) = &entry.server_tls {
+        if let Some(v) = tls.version.as_deref().and_then(|v| v.strip_prefix("TLSv")) {
+            fields.push(("WARC-Protocol", format!("tls/{}", v)));
+        }
+        if let Some(c) = &tls.cipher {
+            fields.push(("WARC-Cipher-Suite", c.clone()));
+        }
+    }
+    fields
+}
+
+fn request_record(entry: &crate::Entry) -> Record {
+    let request = &entry.request;
+    let url = &request.url.url;
+    let body = Body::request(request);
+    let mut fields = vec![
+        ("WARC-Type", String::from("request")),
+        ("WARC-Record-ID", record_id(&entry.uuid("warc-request"))),
+    ];
+    if entry.response.is_some() {
+        fields.push((
+            "WARC-Concurrent-To",
+            record_id(&entry.uuid("warc-response")),
+        ));
+    }
+    fields.extend(common_fields(entry, request.http_version));
+    match &body {
+        Some(b) => fields.push(("WARC-Payload-Digest", digest_field(&b.digest))),
+        // bodies that were not recorded, e.g. ones over the size limit
+        None if request.body.is_some() => fields.push(("WARC-Truncated", String::from("length"))),
+        None => {}
+    }
+    fields.push(("Content-Type", String::from(HTTP_REQUEST)));
+    Record {
+        fields,
+        block: http_message(
+            format!(
+                "{} {} {}",
+                request.method.as_str(),
+                &url[url::Position::BeforePath..url::Position::AfterQuery],
+                wire_version(request.http_version)
+            ),
+            &request.headers,
+            body.as_ref(),
+        ),
+    }
+}
+
+fn response_record(entry: &crate::Entry, response: &response::Response) -> Record {
+    let body = response.content.as_ref().and_then(Body::content);
+    let mut fields = vec![
+        ("WARC-Type", String::from("response")),
+        ("WARC-Record-ID", record_id(&entry.uuid("warc-response"))),
+    ];
+    fields.extend(common_fields(entry, response.http_version));
+    match &body {
+        Some(b) => fields.push(("WARC-Payload-Digest", digest_field(&b.digest))),
+        None if response.content.is_some() => {
+            fields.push(("WARC-Truncated", String::from("length")))
+        }
+        None => {}
+    }
+    fields.push(("Content-Type", String::from(HTTP_RESPONSE)));
+    let reason = http::StatusCode::from_u16(response.status_code.0)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or_default();
+    Record {
+        fields,
+        block: http_message(
+            format!(
+                "{} {} {}",
+                wire_version(response.http_version),
+                response.status_code.0,
+                reason
+            ),
+            &response.headers,
+            body.as_ref(),
+        ),
+    }
+}
+
+// Writes a `warcinfo` record followed by the records of each entry. With
+// `gzip` every record is compressed as its own gzip member, the layout of
+// `.warc.gz` files.
+pub struct WarcWriter<W: Write> {
+    writer: W,
+    gzip: bool,
+}
+impl<W: Write> WarcWriter<W> {
+    pub fn new(writer: W, gzip: bool) -> io::Result<Self> {
+        let mut ret = Self { writer, gzip };
+        let date = warc_date(Utc::now());
+        ret.write_record(&Record {
+            fields: vec![
+                ("WARC-Type", String::from("warcinfo")),
+                (
+                    "WARC-Record-ID",
+                    record_id(&crate::derived_uuid(&[b"warcinfo", date.as_bytes()])),
+                ),
+                ("WARC-Date", date),
+                ("Content-Type", String::from("application/warc-fields")),
+            ],
+            block: format!(
+                "software: {}/{}\r\nformat: WARC File Format 1.1\r\nconformsTo: http://iipc.github.io/warc-specifications/specifications/warc-format/warc-1.1/\r\n",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )
+            .into_bytes(),
+        })?;
+        Ok(ret)
+    }
+    fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        match self.gzip {
+            true => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(&mut self.writer, flate2::Compression::default());
+                record.write(&mut encoder)?;
+                encoder.finish()?;
+                Ok(())
+            }
+            false => record.write(&mut self.writer),
+        }
+    }
+    pub fn write_entry(&mut self, entry: &crate::Entry) -> io::Result<()> {
+        self.write_record(&request_record(entry))?;
+        if let Some(response) = &entry.response {
+            self.write_record(&response_record(entry, response))?;
+        }
+        Ok(())
+    }
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidRecord {
+    #[error("missing or invalid WARC field {0}")]
+    Field(&'static str),
+    #[error("invalid http message: {0}")]
+    Http(&'static str),
+    #[error("invalid request")]
+    Request(
+        #[source]
+        #[from]
+        request::InvalidRequest,
+    ),
+    #[error("invalid response")]
+    Response(
+        #[source]
+        #[from]
+        response::InvalidResponse,
+    ),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("failed to read warc")]
+    Read(
+        #[source]
+        #[from]
+        io::Error,
+    ),
+    #[error("malformed warc record {0}")]
+    Malformed(usize),
+    #[error("invalid warc record {index}")]
+    Record {
+        index: usize,
+        #[source]
+        source: InvalidRecord,
+    },
+    #[error("failed to write recording")]
+    Write(
+        #[source]
+        #[from]
+        WriteError,
+    ),
+}
+
+struct ReadRecord {
+    index: usize,
+    fields: Vec<(String, String)>,
+    block: Vec<u8>,
+}
+impl ReadRecord {
+    fn get<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        self.get_all(name).next()
+    }
+    fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.fields
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    fn id(&self) -> Option<&str> {
+        self.get("WARC-Record-ID")
+    }
+    // pairs are linked from either side, depending on the crawler
+    fn is_concurrent_to(&self, other: &Self) -> bool {
+        let linked = |a: &Self, b: &Self| {
+            b.id()
+                .is_some_and(|id| a.get_all("WARC-Concurrent-To").any(|c| c == id))
+        };
+        linked(self, other) || linked(other, self)
+    }
+    fn target_uri(&self) -> Option<&str> {
+        // WARC 1.0 examples put the URI in angle brackets
+        self.get("WARC-Target-URI")
+            .map(|u| u.trim_start_matches('<').trim_end_matches('>'))
+    }
+    fn date(&self) -> Result<DateTime<Utc>, InvalidRecord> {
+        self.get("WARC-Date")
+            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d.with_timezone(&Utc))
+            .ok_or(InvalidRecord::Field("WARC-Date"))
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    while line.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+        line.pop();
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+fn read_record<R: BufRead>(
+    reader: &mut R,
+    index: usize,
+) -> Result<Option<ReadRecord>, ImportError> {
+    // the blank lines ending the previous record
+    let version = loop {
+        match read_line(reader)? {
+            None => return Ok(None),
+            Some(l) if l.is_empty() => continue,
+            Some(l) => break l,
+        }
+    };
+    if !version.starts_with("WARC/") {
+        return Err(ImportError::Malformed(index));
+    }
+    let mut fields: Vec<(String, String)> = Vec::new();
+    loop {
+        let line = read_line(reader)?.ok_or(ImportError::Malformed(index))?;
+        if line.is_empty() {
+            break;
+        }
+        match (line.starts_with([' ', '\t']), fields.last_mut()) {
+            (true, Some((_, value))) => {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            _ => {
+                let (name, value) = line.split_once(':').ok_or(ImportError::Malformed(index))?;
+                fields.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    let length: u64 = fields
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .ok_or(ImportError::Malformed(index))?;
+    let mut block = Vec::new();
+    reader.take(length).read_to_end(&mut block)?;
+    if block.len() as u64 != length {
+        return Err(ImportError::Malformed(index));
+    }
+    Ok(Some(ReadRecord {
+        index,
+        fields,
+        block,
+    }))
+}
+
+struct HttpMessage<'a> {
+    start_line: &'a str,
+    headers: Vec<(&'a [u8], &'a [u8])>,
+    body: Cow<'a, [u8]>,
+}
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut ret = Vec::new();
+    loop {
+        let end = find(data, b"\r\n")?;
+        let size = std::str::from_utf8(&data[..end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        data = &data[end + 2..];
+        if size == 0 {
+            return Some(ret);
+        }
+        ret.extend_from_slice(data.get(..size)?);
+        data = data.get(size + 2..)?;
+    }
+}
+// Some crawlers store chunked bodies as transferred, they are dechunked here.
+fn parse_http(block: &[u8]) -> Result<HttpMessage<'_>, InvalidRecord> {
+    let (head, body) = match find(block, b"\r\n\r\n") {
+        Some(i) => (&block[..i], &block[i + 4..]),
+        None => (block, &[][..]),
+    };
+    let mut lines = head
+        .split(|b| *b == b'\n')
+        .map(|l| l.strip_suffix(b"\r").unwrap_or(l));
+    let start_line = lines
+        .next()
+        .and_then(|l| std::str::from_utf8(l).ok())
+        .ok_or(InvalidRecord::Http("invalid start line"))?;
+    let headers = lines
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            let colon = l.iter().position(|b| *b == b':')?;
+            Some((l[..colon].trim_ascii(), l[colon + 1..].trim_ascii()))
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(InvalidRecord::Http("invalid header line"))?;
+    let chunked = headers.iter().any(|(n, v)| {
+        n.eq_ignore_ascii_case(b"transfer-encoding")
+            && String::from_utf8_lossy(v)
+                .to_ascii_lowercase()
+                .contains("chunked")
+    });
+    Ok(HttpMessage {
+        start_line,
+        headers,
+        body: match chunked {
+            true => dechunk(body).map_or(Cow::Borrowed(body), Cow::Owned),
+            false => Cow::Borrowed(body),
+        },
+    })
+}
+
+fn http_version<'a>(record: &ReadRecord, wire: &'a str) -> &'a str {
+    let protocols = || record.get_all("WARC-Protocol");
+    if protocols().any(|p| p.eq_ignore_ascii_case("h2")) {
+        HttpVersion::H2.as_str()
+    } else if protocols().any(|p| p.eq_ignore_ascii_case("h3")) {
+        HttpVersion::H3.as_str()
+    } else {
+        wire
+    }
+}
+fn server_tls(record: &ReadRecord) -> Option<tls::Tls> {
+    let version = record
+        .get_all("WARC-Protocol")
+        .find_map(|p| p.strip_prefix("tls/"))?;
+    Some(tls::Tls {
+        version: Some(format!("TLSv{}", version)),
+        cipher: record.get("WARC-Cipher-Suite").map(str::to_string),
+        alpn: None,
+        sni: None,
+        certificates: Vec::new(),
+    })
+}
+
+// Builds the entry of a request record and the response record concurrent to
+// it. A response without its request gets a bare GET request.
+fn to_entry(
+    request: Option<&ReadRecord>,
+    response: Option<&ReadRecord>,
+    index: u32,
+) -> Result<crate::Entry, ImportError> {
+    // either record may carry the connection fields
+    let records = || request.into_iter().chain(response);
+    let first = request
+        .or(response)
+        .expect("a request or a response record");
+    let invalid = |source| ImportError::Record {
+        index: first.index,
+        source,
+    };
+    let url = first
+        .target_uri()
+        .ok_or(invalid(InvalidRecord::Field("WARC-Target-URI")))?;
+    let request = match request {
+        Some(r) => {
+            let message = parse_http(&r.block).map_err(invalid)?;
+            let mut parts = message.start_line.split_whitespace();
+            let (method, version) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(m), Some(_), Some(v)) => (m, v),
+                _ => return Err(invalid(InvalidRecord::Http("invalid request line"))),
+            };
+            request::Request::parse(
+                http_version(r, version),
+                method,
+                url,
+                message.headers.into_iter(),
+                Some(Payload::Raw(&message.body)),
+            )
+            .map_err(|e| invalid(e.into()))?
+        }
+        None => request::Request::parse(
+            HttpVersion::Http11.as_str(),
+            "GET",
+            url,
+            std::iter::empty::<(&[u8], &[u8])>(),
+            None,
+        )
+        .map_err(|e| invalid(e.into()))?,
+    };
+    let parsed_response = match response {
+        Some(r) => {
+            let message = parse_http(&r.block).map_err(invalid)?;
+            let mut parts = message.start_line.split_whitespace();
+            let (version, status) = match (parts.next(), parts.next().map(str::parse)) {
+                (Some(v), Some(Ok(s))) => (v, s),
+                _ => return Err(invalid(InvalidRecord::Http("invalid status line"))),
+            };
+            Some(
+                response::Response::parse(
+                    http_version(r, version),
+                    status,
+                    url,
+                    message.headers.into_iter(),
+                    Some(Payload::Raw(&message.body)),
+                )
+                .map_err(|e| invalid(e.into()))?,
+            )
+        }
+        None => None,
+    };
+    let start_time = first.date().map_err(invalid)?;
+    let finish_time = match response {
+        Some(r) => r.date().map_err(invalid)?.max(start_time),
+        None => start_time,
+    };
+    let server_addr = match records().find_map(|r| r.get("WARC-IP-Address")) {
+        Some(a) => Some(SocketAddr::new(
+            a.parse::<IpAddr>()
+                .map_err(|_| invalid(InvalidRecord::Field("WARC-IP-Address")))?,
+            request.url.url.port_or_known_default().unwrap_or_default(),
+        )),
+        None => None,
+    };
+    Ok(crate::Entry {
+        version: crate::VERSION,
+        index,
+        // the client is not part of the records
+        client_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
+        server_addr,
+        client_tls: None,
+        server_tls: records().find_map(server_tls),
+        timings: crate::Timings {
+            start_time,
+            finish_time,
+            phases: None,
+        },
+        request,
+        response: parsed_response,
+        error: None,
+        websocket: None,
+        redactions: Vec::new(),
+    })
+}
+
+fn is_http(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/http"))
+}
+
+// The request and response of the entry of a record without its other half.
+fn unpaired(record: &ReadRecord) -> (Option<&ReadRecord>, Option<&ReadRecord>) {
+    match record.get("WARC-Type") {
+        Some("request") => (Some(record), None),
+        _ => (None, Some(record)),
+    }
+}
+
+/// Unpaired records kept while importing. Writers put the two halves of a
+/// pair next to each other, the request or the response first, so older
+/// records are written without their other half rather than held to the end.
+const MAX_PENDING: usize = 64;
+
+// Converts the request and response records of a WARC file, plain or gzip
+// compressed, into a recording at `dest`, in the order their pairs complete.
+// Other record types and records not holding an HTTP message are skipped.
+// Returns None when there are no HTTP records. The entries before an invalid
+// record are kept as a finished recording.
+pub fn import<R: Read, P: AsRef<Path>>(
+    reader: R,
+    dest: P,
+    options: WriteOptions,
+) -> Result<Option<archive::Info>, ImportError> {
+    let mut reader = io::BufReader::new(reader);
+    let mut reader: Box<dyn BufRead> = match reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        true => Box::new(io::BufReader::new(flate2::read::MultiGzDecoder::new(
+            reader,
+        ))),
+        false => Box::new(reader),
+    };
+    write_recording(dest.as_ref(), options, |write_entry| {
+        let mut count = 0;
+        let mut write = |request: Option<&ReadRecord>, response: Option<&ReadRecord>| {
+            let entry = to_entry(request, response, count)?;
+            count += 1;
+            write_entry(&entry).map_err(ImportError::from)
+        };
+        // records still waiting for the other half of their pair, oldest first
+        let mut pending: VecDeque<ReadRecord> = VecDeque::new();
+        let mut index = 0;
+        while let Some(record) = read_record(&mut reader, index)? {
+            index += 1;
+            let is_request = match record.get("WARC-Type") {
+                Some("request") => true,
+                Some("response") => false,
+                _ => continue,
+            };
+            // e.g. the DNS answers of `dns:` responses
+            if !record.get("Content-Type").is_some_and(is_http) {
+                continue;
+            }
+            // records without any link are paired by target URI
+            let unlinked = |r: &ReadRecord| r.get("WARC-Concurrent-To").is_none();
+            let pair = pending.iter().position(|p| {
+                (p.get("WARC-Type") == Some("request")) != is_request
+                    && (record.is_concurrent_to(p)
+                        || (unlinked(&record)
+                            && unlinked(p)
+                            && p.target_uri() == record.target_uri()))
+            });
+            match pair {
+                Some(i) => {
+                    let other = pending.remove(i).unwrap();
+                    match is_request {
+                        true => write(Some(&record), Some(&other))?,
+                        false => write(Some(&other), Some(&record))?,
+                    }
+                }
+                None => {
+                    pending.push_back(record);
+                    if pending.len() > MAX_PENDING {
+                        let oldest = pending.pop_front().unwrap();
+                        let (request, response) = unpaired(&oldest);
+                        write(request, response)?;
+                    }
+                }
+            }
+        }
+        for record in &pending {
+            let (request, response) = unpaired(record);
+            write(request, response)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base32_rfc4648() {
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "MY======"),
+            ("fo", "MZXQ===="),
+            ("foo", "MZXW6==="),
+            ("foob", "MZXW6YQ="),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI======"),
+        ] {
+            assert_eq!(base32(data.as_bytes()), encoded);
+        }
+        assert_eq!(
+            digest_field(&sha256(b"")),
+            "sha256:4OYMIQUY7QOBJGX36TEJS35ZEQT24QPEMSNZGTFESWMRW6CSXBKQ===="
+        );
+    }
+
+    #[test]
+    fn dechunk_body() {
+        assert_eq!(
+            dechunk(b"4\r\nWiki\r\n5;name=value\r\npedia\r\n0\r\n\r\n").as_deref(),
+            Some(&b"Wikipedia"[..])
+        );
+        assert_eq!(dechunk(b"0\r\n\r\n").as_deref(), Some(&b""[..]));
+        // missing last chunk, short chunk and invalid size
+        assert_eq!(dechunk(b"4\r\nWiki\r\n"), None);
+        assert_eq!(dechunk(b"5\r\nWiki\r\n0\r\n\r\n"), None);
+        assert_eq!(dechunk(b"x\r\nWiki\r\n0\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn read_records() {
+        let data = b"\r\nWARC/1.1\r\nWARC-Type: response\r\nWARC-Protocol: h2\r\n\
+            WARC-Target-URI: <https://example.com/\r\n  a>\r\nContent-Length: 4\r\n\r\n\
+            body\r\n\r\nWARC/1.0\r\nWARC-Type: metadata\r\nContent-Length: 0\r\n\r\n\r\n\r\n";
+        let mut reader = &data[..];
+        let record = read_record(&mut reader, 0).unwrap().unwrap();
+        assert_eq!(record.get("warc-type"), Some("response"));
+        assert_eq!(record.target_uri(), Some("https://example.com/ a"));
+        assert_eq!(record.block, b"body");
+        let record = read_record(&mut reader, 1).unwrap().unwrap();
+        assert_eq!(record.index, 1);
+        assert_eq!(record.get("WARC-Type"), Some("metadata"));
+        assert!(record.block.is_empty());
+        assert!(read_record(&mut reader, 2).unwrap().is_none());
+
+        for data in [
+            &b"HTTP/1.1 200 OK\r\n\r\n"[..],
+            b"WARC/1.1\r\nWARC-Type: response\r\n\r\n",
+            b"WARC/1.1\r\nContent-Length: 10\r\n\r\nbody",
+        ] {
+            assert!(matches!(
+                read_record(&mut &data[..], 0),
+                Err(ImportError::Malformed(0))
+            ));
+        }
+    }
+}
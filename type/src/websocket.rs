@@ -0,0 +1,90 @@
+//! Messages exchanged over a connection upgraded to WebSocket.
+//!
+//! Messages belong to the entry of the upgrading request. In packs they are
+//! stored apart from `entry.bin` in the entry's
+//! [`WEBSOCKET_MESSAGES`](crate::archive::WEBSOCKET_MESSAGES) file.
+
+use crate::response::Segment;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+impl Opcode {
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x0 => Some(Self::Continuation),
+            0x1 => Some(Self::Text),
+            0x2 => Some(Self::Binary),
+            0x8 => Some(Self::Close),
+            0x9 => Some(Self::Ping),
+            0xa => Some(Self::Pong),
+            _ => None,
+        }
+    }
+    pub const fn code(&self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xa,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketMessage {
+    pub direction: Direction,
+    pub opcode: Opcode,
+    #[serde(with = "serde_bytes")]
+    pub data: Box<[u8]>,
+    #[serde(with = "crate::serde_date_time")]
+    pub time: chrono::DateTime<chrono::Utc>,
+}
+impl WebSocketMessage {
+    /// The payload of a text message.
+    pub fn text(&self) -> Option<&str> {
+        match self.opcode {
+            Opcode::Text => std::str::from_utf8(&self.data).ok(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebSocket {
+    /// `None` if the connection was not closed with a close frame
+    pub close_code: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close_reason: Option<String>,
+    pub closed_by_client: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<WebSocketMessage>,
+    /// set if the messages are a segment of those of a long-lived connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment: Option<Segment>,
+}
+impl WebSocket {
+    /// Total payload size of the messages sent in `direction`.
+    pub fn size(&self, direction: Direction) -> u64 {
+        self.messages
+            .iter()
+            .filter(|m| m.direction == direction)
+            .map(|m| m.data.len() as u64)
+            .sum()
+    }
+}
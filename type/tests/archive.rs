@@ -0,0 +1,152 @@
+use http_recorder::{
+    archive::{
+        pack_file_name,
+        reader::ReadError,
+        writer::{Rotation, WriteOptions},
+        Codec, Compression, RecordingReader, RecordingWriter,
+    },
+    mitmproxy::{flow::Flow, FlowReader},
+    Entry,
+};
+use std::fs;
+
+// Copies of the first flow of the fixture, each with a response body of its
+// own.
+fn entries(indexes: std::ops::Range<u32>, body_size: usize) -> Vec<Entry> {
+    let state = FlowReader::new(&include_bytes!("data/flows.mitm")[..])
+        .next()
+        .unwrap()
+        .unwrap();
+    let entry = Flow::from_state(&state).unwrap().into_entry(0).unwrap();
+    indexes
+        .map(|index| {
+            let mut entry = entry.clone();
+            entry.index = index;
+            let body: Vec<u8> = (0..body_size)
+                .map(|b| (b / 7) as u8 ^ index as u8)
+                .collect();
+            entry
+                .response
+                .as_mut()
+                .unwrap()
+                .content
+                .as_mut()
+                .unwrap()
+                .set_data(body.into_boxed_slice());
+            entry
+        })
+        .collect()
+}
+
+fn response_data(entry: &Entry) -> &[u8] {
+    entry
+        .response
+        .as_ref()
+        .unwrap()
+        .content
+        .as_ref()
+        .unwrap()
+        .data
+        .as_deref()
+        .unwrap()
+}
+
+fn options(codec: Codec, max_entries: Option<u32>) -> WriteOptions {
+    WriteOptions {
+        dedup: false,
+        compression: Compression::new(codec, None).unwrap(),
+        rotation: Rotation {
+            max_size: None,
+            max_entries,
+            interval: None,
+        },
+    }
+}
+
+#[test]
+fn write_and_get() {
+    let entries = entries(0..8, 3000);
+    for codec in [Codec::Xz, Codec::Zstd, Codec::None] {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording");
+        let mut writer =
+            RecordingWriter::with_options(&path, &entries[0], options(codec, Some(3))).unwrap();
+        for entry in &entries {
+            writer.add_entry(entry).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.pack_count(), 3, "{:?}", codec);
+        assert_eq!(reader.info().count, 8);
+        for entry in &entries {
+            let read = reader.get(entry.index).unwrap();
+            assert_eq!(read.index, entry.index);
+            assert_eq!(response_data(&read), response_data(entry), "{:?}", codec);
+        }
+        assert!(matches!(reader.get(8), Err(ReadError::NotFound(8))));
+        let read = reader.entries().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            read.iter().map(|e| e.index).collect::<Vec<_>>(),
+            (0..8).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn resume_after_truncated_pack() {
+    // a body is packed as a file and in entry.bin, so the first three entries
+    // fill a 16 MiB stream, which gets journaled once the fourth starts the
+    // next one
+    let entries = entries(0..6, 3 << 20);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("recording");
+    let options = options(Codec::Zstd, None);
+    let mut writer = RecordingWriter::with_options(&path, &entries[0], options.clone()).unwrap();
+    for entry in &entries[..5] {
+        writer.add_entry(entry).unwrap();
+    }
+    // a crash in the middle of the second stream
+    drop(writer);
+    let reader = RecordingReader::open(&path).unwrap();
+    let journal = reader.journal().unwrap().unwrap();
+    assert_eq!(journal.records.len(), 3);
+    let pack = path.join(pack_file_name(0, Codec::Zstd));
+    let end = journal.records[2].end;
+    assert!(fs::metadata(&pack).unwrap().len() > end);
+    fs::File::options()
+        .write(true)
+        .open(&pack)
+        .unwrap()
+        .set_len(end + 100)
+        .unwrap();
+
+    let journaled = reader
+        .journaled_entries(&journal)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        journaled.iter().map(|e| e.index).collect::<Vec<_>>(),
+        [0, 1, 2]
+    );
+    assert_eq!(
+        response_data(&reader.get(1).unwrap()),
+        response_data(&entries[1])
+    );
+    assert!(matches!(reader.get(3), Err(ReadError::NotFound(3))));
+
+    let mut writer = RecordingWriter::resume(&path, &entries[5], options).unwrap();
+    writer.add_entry(&entries[5]).unwrap();
+    writer.finish().unwrap();
+    let reader = RecordingReader::open(&path).unwrap();
+    assert_eq!(reader.pack_count(), 2);
+    assert_eq!(fs::metadata(&pack).unwrap().len(), end);
+    let read = reader.entries().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        read.iter().map(|e| e.index).collect::<Vec<_>>(),
+        [0, 1, 2, 5]
+    );
+    assert_eq!(response_data(&read[2]), response_data(&entries[2]));
+    assert_eq!(response_data(&read[3]), response_data(&entries[5]));
+}
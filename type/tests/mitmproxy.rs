@@ -0,0 +1,214 @@
+use http_recorder::{
+    content::{ContentCoding, Payload},
+    header,
+    mitmproxy::{flow::Flow, tnetstring, FlowReader, FlowWriter},
+    request::{Body, Request},
+    Entry, Method,
+};
+use std::io;
+
+// Two HTTP flows, a TCP flow and a failed HTTP flow in the flow format 20
+// state layout of mitmproxy 10.
+const FLOWS: &[u8] = include_bytes!("data/flows.mitm");
+
+fn entries(mut reader: impl io::BufRead) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    while let Some(state) = tnetstring::load(&mut reader).unwrap() {
+        if state.get("type").and_then(tnetstring::Value::as_str) != Some("http") {
+            continue;
+        }
+        let index = entries.len() as u32;
+        entries.push(Flow::from_state(&state).unwrap().into_entry(index).unwrap());
+    }
+    entries
+}
+
+#[test]
+fn load_dump() {
+    let entries = entries(FLOWS);
+    assert_eq!(entries.len(), 3);
+
+    let get = &entries[0];
+    assert_eq!(get.request.method, Method::Get);
+    assert_eq!(
+        get.request.url.url.as_str(),
+        "https://example.com/api/items?page=2"
+    );
+    assert_eq!(get.server_addr, Some("93.184.216.34:443".parse().unwrap()));
+    let tls = get.server_tls.as_ref().unwrap();
+    assert_eq!(tls.version.as_deref(), Some("TLSv1.3"));
+    assert_eq!(tls.alpn.as_deref(), Some("http/1.1"));
+    let response = get.response.as_ref().unwrap();
+    assert_eq!(response.status_code, 200);
+    let content = response.content.as_ref().unwrap();
+    assert_eq!(content.content_type.essence_str(), "application/json");
+    assert!(content.encoding.as_ref().is_some_and(|e| e.decoded));
+    let data = content.data.as_deref().unwrap();
+    assert!(data.starts_with(br#"{"items": [{"id": 1"#));
+    assert_eq!(content.size, data.len() as u64);
+
+    let post = &entries[1];
+    assert_eq!(post.request.method, Method::Post);
+    assert!(post.client_tls.is_none());
+    let cookies: Vec<_> = post
+        .request
+        .cookies
+        .0
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    assert_eq!(cookies, ["session", "theme"]);
+    match &post.request.body {
+        Some(Body::UrlEncodedForm(form)) => {
+            let form: Vec<_> = form
+                .iter()
+                .map(|e| (e.name.as_str(), e.value.as_str()))
+                .collect();
+            assert_eq!(
+                form,
+                [
+                    ("user", "alice"),
+                    ("password", "s3cret"),
+                    ("remember", "on")
+                ]
+            );
+        }
+        body => panic!("unexpected body {:?}", body),
+    }
+    let response = post.response.as_ref().unwrap();
+    assert_eq!(response.status_code, 302);
+    assert_eq!(response.cookies.0[0].value, "def456");
+
+    let failed = &entries[2];
+    assert_eq!(failed.index, 2);
+    assert_eq!(
+        failed.request.url.url.as_str(),
+        "http://unreachable.test:8000/"
+    );
+    assert!(failed.server_addr.is_none());
+    assert!(failed.response.is_none());
+    assert_eq!(failed.error.as_ref().unwrap().message, "Connection refused");
+}
+
+fn dump(entries: &[Entry]) -> Vec<u8> {
+    let mut writer = FlowWriter::new(Vec::new());
+    for entry in entries.iter() {
+        writer.write_entry(entry).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+// Encoded bodies are compressed again on export and come out with other
+// bytes than the ones transferred, only their decoded content is kept.
+fn normalized(entry: &Entry) -> serde_json::Value {
+    let mut entry = entry.clone();
+    if let Some(response) = entry.response.as_mut() {
+        response
+            .headers
+            .0
+            .retain(|h| h.name != header::CONTENT_LENGTH);
+        if let Some(encoding) = response.content.as_mut().and_then(|c| c.encoding.as_mut()) {
+            encoding.encoded_size = None;
+        }
+    }
+    serde_json::to_value(entry).unwrap()
+}
+
+#[test]
+fn write_and_read_back() {
+    let entries = entries(FLOWS);
+    let dump = dump(&entries);
+    let states = FlowReader::new(dump.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(states.len(), entries.len());
+    let read: Vec<_> = states
+        .iter()
+        .enumerate()
+        .map(|(index, state)| {
+            Flow::from_state(state)
+                .unwrap()
+                .into_entry(index as u32)
+                .unwrap()
+        })
+        .collect();
+    for (index, (read, entry)) in read.iter().zip(entries.iter()).enumerate() {
+        assert_eq!(normalized(read), normalized(entry), "entry {}", index);
+        let data = |e: &Entry| {
+            e.response
+                .as_ref()
+                .and_then(|r| r.content.as_ref())
+                .and_then(|c| c.data.clone())
+        };
+        assert_eq!(data(read), data(entry), "entry {}", index);
+    }
+    assert_eq!(self::dump(&read), dump);
+}
+
+#[test]
+fn encoded_form() {
+    let mut entries = entries(FLOWS);
+    entries.truncate(2);
+    let raw = ContentCoding::encode_all(&[ContentCoding::Gzip], b"user=alice&remember=on").unwrap();
+    entries[1].request = Request::parse(
+        "HTTP/1.1",
+        "POST",
+        "http://example.com/login",
+        [
+            ("Content-Type", "application/x-www-form-urlencoded"),
+            ("Content-Encoding", "gzip"),
+        ]
+        .into_iter(),
+        Some(Payload::Raw(&raw)),
+    )
+    .unwrap();
+    let encoding = entries[1].request.form_encoding.clone().unwrap();
+    assert_eq!(encoding.codings, [ContentCoding::Gzip]);
+    assert_eq!(encoding.encoded_size, Some(raw.len() as u64));
+
+    let dump = dump(&entries);
+    let state = FlowReader::new(dump.as_slice()).nth(1).unwrap().unwrap();
+    let read = Flow::from_state(&state).unwrap().into_entry(1).unwrap();
+    match &read.request.body {
+        Some(Body::UrlEncodedForm(form)) => assert_eq!(form[0].value, "alice"),
+        body => panic!("unexpected body {:?}", body),
+    }
+    let read = read.request.form_encoding.unwrap();
+    assert_eq!(read.codings, encoding.codings);
+    assert!(read.decoded);
+
+    // a form that does not decode is kept as it was transferred
+    let request = Request::parse(
+        "HTTP/1.1",
+        "POST",
+        "http://example.com/login",
+        [
+            ("Content-Type", "application/x-www-form-urlencoded"),
+            ("Content-Encoding", "gzip"),
+        ]
+        .into_iter(),
+        Some(Payload::Raw(b"user=alice")),
+    )
+    .unwrap();
+    assert!(request.form_encoding.is_none());
+    match request.body {
+        Some(Body::Content(c)) => assert!(c.encoding.is_some_and(|e| !e.decoded)),
+        body => panic!("unexpected body {:?}", body),
+    }
+}
+
+#[test]
+fn nesting_limit() {
+    let nested = |depth: usize| {
+        let mut value = b"0:]".to_vec();
+        for _ in 1..depth {
+            value = [format!("{}:", value.len()).as_bytes(), &value, b"]"].concat();
+        }
+        value
+    };
+    assert!(tnetstring::load(&mut nested(256).as_slice()).is_ok());
+    assert!(matches!(
+        tnetstring::load(&mut nested(257).as_slice()),
+        Err(tnetstring::LoadError::Invalid("nesting too deep"))
+    ));
+}
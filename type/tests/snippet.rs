@@ -0,0 +1,164 @@
+use http_recorder::{
+    content::Payload,
+    header::Headers,
+    request::{Body, MultipartFormEntry, Request},
+    snippet::{generate, Language},
+};
+
+fn request(method: &str, url: &str, headers: &[(&str, &str)], body: &[u8]) -> Request {
+    Request::parse(
+        "HTTP/1.1",
+        method,
+        url,
+        headers.iter().copied(),
+        Some(Payload::Raw(body)),
+    )
+    .unwrap()
+}
+
+fn assert_snippets(request: &Request, curl: &str, httpie: &str, reqwest: &str) {
+    assert_eq!(generate(request, Language::Curl), curl);
+    assert_eq!(generate(request, Language::Httpie), httpie);
+    assert_eq!(generate(request, Language::Reqwest), reqwest);
+}
+
+#[test]
+fn no_body() {
+    let request = request(
+        "GET",
+        "https://example.com/search?q=a%20b",
+        &[
+            ("Host", "example.com"),
+            ("Accept-Encoding", "gzip"),
+            ("X-Empty", ""),
+            ("Cookie", "session=abc; theme=dark"),
+        ],
+        b"",
+    );
+    assert_snippets(
+        &request,
+        r#"curl 'https://example.com/search?q=a%20b' \
+  -H 'Accept-Encoding: gzip' \
+  -H 'x-empty;' \
+  --compressed \
+  -b 'session=abc; theme=dark'
+"#,
+        r#"http GET 'https://example.com/search?q=a%20b' \
+  Accept-Encoding:gzip \
+  'x-empty;' \
+  'Cookie:session=abc; theme=dark'
+"#,
+        r#"let response = reqwest::blocking::Client::new()
+    .request(reqwest::Method::GET, "https://example.com/search?q=a%20b")
+    .header("Accept-Encoding", "gzip")
+    .header("x-empty", "")
+    .header("Cookie", "session=abc; theme=dark")
+    .send()?;
+"#,
+    );
+}
+
+#[test]
+fn raw_body() {
+    let mut request = request(
+        "PUT",
+        "https://example.com/api/items/1",
+        &[("Content-Type", "application/json")],
+        br#"{"name": "it's"}"#,
+    );
+    // not a cookie pair, curl's -b would read it as a file name
+    let cookie = Headers::parse([("Cookie", "flag")].into_iter()).unwrap();
+    request.headers.0.extend(cookie.0);
+    assert_snippets(
+        &request,
+        r#"curl -X PUT https://example.com/api/items/1 \
+  -H 'Content-Type: application/json' \
+  -H 'Cookie: flag' \
+  --data-raw '{"name": "it'\''s"}'
+"#,
+        r#"http PUT https://example.com/api/items/1 \
+  --raw '{"name": "it'\''s"}' \
+  Content-Type:application/json \
+  Cookie:flag
+"#,
+        r#"let response = reqwest::blocking::Client::new()
+    .request(reqwest::Method::PUT, "https://example.com/api/items/1")
+    .header("Content-Type", "application/json")
+    .header("Cookie", "flag")
+    .body("{\"name\": \"it's\"}")
+    .send()?;
+"#,
+    );
+}
+
+#[test]
+fn url_encoded_form() {
+    let request = request(
+        "POST",
+        "http://example.com:8080/login",
+        &[("Content-Type", "application/x-www-form-urlencoded")],
+        b"user=alice&pass=it%27s+%26&we+ird%40=1&=x",
+    );
+    assert_snippets(
+        &request,
+        r#"curl http://example.com:8080/login \
+  --data-urlencode user=alice \
+  --data-urlencode 'pass=it'\''s &' \
+  --data-urlencode we+ird%40=1 \
+  --data-raw =x
+"#,
+        r#"http --form POST http://example.com:8080/login \
+  user=alice \
+  'pass=it'\''s &' \
+  'we ird\@=1' \
+  =x
+"#,
+        r#"let response = reqwest::blocking::Client::new()
+    .request(reqwest::Method::POST, "http://example.com:8080/login")
+    .form(&[
+        ("user", "alice"),
+        ("pass", "it's &"),
+        ("we ird@", "1"),
+        ("", "x"),
+    ])
+    .send()?;
+"#,
+    );
+}
+
+#[test]
+fn multipart_form() {
+    let url = "https://example.com/upload";
+    let content_type = "multipart/form-data; boundary=XX";
+    let mut request = request("POST", url, &[("Content-Type", content_type)], b"");
+    let form = MultipartFormEntry::parse(
+        url,
+        content_type,
+        b"--XX\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\n\
+        it's;type=text/html\r\n--XX\r\n\
+        Content-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\
+        Content-Type: text/plain\r\n\r\nfile data\r\n--XX--\r\n",
+    )
+    .unwrap();
+    request.body = Some(Body::MultipartForm(form));
+    assert_snippets(
+        &request,
+        r#"curl https://example.com/upload \
+  --form-string 'note=it'\''s;type=text/html' \
+  -F 'upload=@a.txt;type=text/plain'
+"#,
+        r#"http --multipart POST https://example.com/upload \
+  'note=it'\''s;type=text/html' \
+  'upload@a.txt;type=text/plain'
+"#,
+        r#"let response = reqwest::blocking::Client::new()
+    .request(reqwest::Method::POST, "https://example.com/upload")
+    .multipart(
+        reqwest::blocking::multipart::Form::new()
+            .text("note", "it's;type=text/html")
+            .file("upload", "a.txt")?,
+    )
+    .send()?;
+"#,
+    );
+}
@@ -0,0 +1,173 @@
+use http_recorder::{
+    archive::{writer::WriteOptions, RecordingReader},
+    mitmproxy::{flow::Flow, FlowReader},
+    request::Body,
+    warc::{self, WarcWriter},
+    Entry,
+};
+
+fn entries() -> Vec<Entry> {
+    FlowReader::new(&include_bytes!("data/flows.mitm")[..])
+        .map(Result::unwrap)
+        .filter(|s| s.get("type").and_then(|t| t.as_str()) == Some("http"))
+        .enumerate()
+        .map(|(index, state)| {
+            Flow::from_state(&state)
+                .unwrap()
+                .into_entry(index as u32)
+                .unwrap()
+        })
+        .collect()
+}
+
+fn export(entries: &[Entry], gzip: bool) -> Vec<u8> {
+    let mut writer = WarcWriter::new(Vec::new(), gzip).unwrap();
+    for entry in entries {
+        writer.write_entry(entry).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+fn import(data: &[u8]) -> (tempfile::TempDir, Result<Vec<Entry>, warc::ImportError>) {
+    let dir = tempfile::tempdir().unwrap();
+    let dest = dir.path().join("recording");
+    let ret = warc::import(data, &dest, WriteOptions::default());
+    let entries = || {
+        RecordingReader::open(&dest)
+            .unwrap()
+            .entries()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    };
+    let ret = ret.map(|info| match info {
+        Some(_) => entries(),
+        None => Vec::new(),
+    });
+    (dir, ret)
+}
+
+fn response_data(entry: &Entry) -> Option<&[u8]> {
+    entry.response.as_ref()?.content.as_ref()?.data.as_deref()
+}
+
+#[test]
+fn export_and_import() {
+    let entries = entries();
+    for gzip in [false, true] {
+        let (_dir, read) = import(&export(&entries, gzip));
+        let read = read.unwrap();
+        assert_eq!(read.len(), entries.len());
+        for (read, entry) in read.iter().zip(entries.iter()) {
+            assert_eq!(read.request.method, entry.request.method);
+            assert_eq!(read.request.url.url, entry.request.url.url);
+            assert_eq!(read.server_addr, entry.server_addr);
+            assert_eq!(
+                read.server_tls.as_ref().map(|t| (&t.version, &t.cipher)),
+                entry.server_tls.as_ref().map(|t| (&t.version, &t.cipher))
+            );
+            assert_eq!(
+                read.response.as_ref().map(|r| r.status_code),
+                entry.response.as_ref().map(|r| r.status_code)
+            );
+            assert_eq!(response_data(read), response_data(entry));
+        }
+        match &read[1].request.body {
+            Some(Body::UrlEncodedForm(form)) => assert_eq!(form[1].value, "s3cret"),
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+}
+
+#[test]
+fn export_without_body() {
+    let mut entries = entries();
+    entries.truncate(1);
+    let content = entries[0]
+        .response
+        .as_mut()
+        .unwrap()
+        .content
+        .as_mut()
+        .unwrap();
+    content.data = None;
+    let data = export(&entries, false);
+    let text = String::from_utf8_lossy(&data);
+    let response = &text[text.find("WARC-Type: response").unwrap()..];
+    assert!(response.contains("WARC-Truncated: length\r\n"));
+    assert!(!response.contains("WARC-Payload-Digest"));
+    // the Content-Length and Content-Encoding of the transferred body
+    assert!(response.contains("Content-Encoding: gzip\r\nContent-Length: 79\r\n"));
+
+    let (_dir, read) = import(&data);
+    let read = read.unwrap();
+    assert_eq!(read[0].response.as_ref().unwrap().status_code, 200);
+    assert_eq!(response_data(&read[0]), None);
+}
+
+#[test]
+fn import_skips_other_records() {
+    let mut data = export(&entries()[..1], false);
+    data.extend_from_slice(
+        b"WARC/1.1\r\nWARC-Type: response\r\nWARC-Target-URI: dns:example.com\r\n\
+        WARC-Date: 2023-11-14T22:13:20Z\r\nContent-Type: text/dns\r\nContent-Length: 37\r\n\r\n\
+        20231114221320\nexample.com. 60 IN A 1\r\n\r\n",
+    );
+    let (_dir, read) = import(&data);
+    assert_eq!(read.unwrap().len(), 1);
+}
+
+#[test]
+fn import_keeps_entries_before_an_invalid_record() {
+    let mut data = export(&entries()[..2], false);
+    data.extend_from_slice(
+        b"WARC/1.1\r\nWARC-Type: response\r\nWARC-Target-URI: http://example.com/\r\n\
+        WARC-Date: 2023-11-14T22:13:20Z\r\nContent-Type: application/http;msgtype=response\r\n\
+        Content-Length: 8\r\n\r\nHTTP/1.1\r\n\r\n",
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let dest = dir.path().join("recording");
+    let ret = warc::import(&data[..], &dest, WriteOptions::default());
+    assert!(matches!(ret, Err(warc::ImportError::Record { .. })));
+    let reader = RecordingReader::open(&dest).unwrap();
+    assert_eq!(reader.info().count, 2);
+}
+
+#[test]
+fn import_writes_old_unpaired_records() {
+    let mut data = Vec::new();
+    for i in 0..70 {
+        let block = "HTTP/1.1 204 No Content\r\n\r\n";
+        data.extend_from_slice(
+            format!(
+                "WARC/1.1\r\nWARC-Type: response\r\nWARC-Target-URI: http://example.com/{}\r\n\
+                WARC-Date: 2023-11-14T22:13:20Z\r\nContent-Type: application/http;msgtype=response\r\n\
+                Content-Length: {}\r\n\r\n{}\r\n\r\n",
+                i,
+                block.len(),
+                block
+            )
+            .as_bytes(),
+        );
+    }
+    let entries = entries();
+    data.extend_from_slice(&export(&entries[..2], false));
+    let (_dir, read) = import(&data);
+    let read = read.unwrap();
+    assert_eq!(read.len(), 72);
+    // the oldest records are written once too many wait for a pair, the
+    // pairs as they complete and the other records at the end
+    let unpaired: Vec<_> = read
+        .iter()
+        .filter_map(|e| e.request.url.url.path()[1..].parse::<u32>().ok())
+        .collect();
+    assert_eq!(unpaired, (0..70).collect::<Vec<_>>());
+    let paired = read
+        .iter()
+        .position(|e| e.request.url.url == entries[0].request.url.url)
+        .unwrap();
+    assert_eq!(read[0].request.url.url.path(), "/0");
+    for (read, entry) in read[paired..paired + 2].iter().zip(entries.iter()) {
+        assert_eq!(read.request.url.url, entry.request.url.url);
+        assert_eq!(response_data(read), response_data(entry));
+    }
+}